@@ -0,0 +1,651 @@
+use std::collections::BTreeMap;
+use std::path::{Component, Path, PathBuf};
+
+use futures::future::join_all;
+use reqwest::{header, Client, Url};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tokio::sync::Semaphore;
+
+use crate::notion;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Policy {
+    /// Download files uploaded to Notion
+    Internal,
+    /// Download files uploaded to Notion and external files
+    All,
+    /// Download nothing
+    None,
+}
+
+#[derive(clap::Args)]
+pub struct Options {
+    /// Download files into this directory and link to them
+    #[clap(long, global = true)]
+    pub assets_dir: Option<PathBuf>,
+    /// Which files to download into --assets-dir
+    #[clap(long, global = true, value_enum, default_value = "internal")]
+    pub assets: Policy,
+    /// Download external files only from this domain or its subdomains (repeatable)
+    #[clap(long, global = true, value_parser = parse_domain)]
+    pub assets_allow_domain: Vec<String>,
+    /// Never download external files from this domain or its subdomains (repeatable)
+    #[clap(long, global = true, value_parser = parse_domain)]
+    pub assets_deny_domain: Vec<String>,
+    /// Keep linking to files larger than this many bytes instead of downloading them
+    #[clap(long, global = true)]
+    pub assets_max_size: Option<u64>,
+    /// Maximum number of concurrent downloads
+    #[clap(long, global = true, default_value = "4", value_parser = clap::value_parser!(u64).range(1..))]
+    pub assets_concurrency: u64,
+    /// Write the list of downloaded files to this JSON file
+    #[clap(long, global = true)]
+    pub assets_manifest: Option<PathBuf>,
+}
+
+/// Returns `s` in the lowercase form of hosts in urls, also accepting `.example.com`.
+fn parse_domain(s: &str) -> Result<String, String> {
+    let domain = s.trim_start_matches('.').to_lowercase();
+    if domain.is_empty() {
+        return Err(format!("invalid domain: {}", s));
+    }
+    Ok(domain)
+}
+
+/// A downloaded file, listed in the manifest under its original url.
+#[derive(Debug, Serialize)]
+pub struct Asset {
+    pub path: String,
+    pub sha256: String,
+    pub size: u64,
+}
+
+pub type Manifest = BTreeMap<String, Asset>;
+
+impl Options {
+    fn accepts(&self, url: &str, internal: bool) -> bool {
+        match self.assets {
+            Policy::None => return false,
+            Policy::Internal if !internal => return false,
+            _ if internal => return true,
+            _ => {}
+        }
+        let host = match Url::parse(url)
+            .ok()
+            .and_then(|u| u.host_str().map(String::from))
+        {
+            Some(host) => host,
+            None => return false,
+        };
+        let matches = |domain: &String| host == *domain || host.ends_with(&format!(".{}", domain));
+        !self.assets_deny_domain.iter().any(matches)
+            && (self.assets_allow_domain.is_empty() || self.assets_allow_domain.iter().any(matches))
+    }
+}
+
+/// Downloads files allowed by `options` into `options.assets_dir`, since the
+/// signed urls of files uploaded to Notion expire in an hour and external
+/// files may disappear, and rewrites the urls to the downloaded files.
+///
+/// Files are named after the id of the block (or page for cover and icon)
+/// so that exporting the same page twice gives the same paths.
+/// Rewritten urls are relative to `base`, the directory of the document.
+pub async fn download(
+    page: &mut notion::Page,
+    meta: &mut notion::PageMeta,
+    options: &Options,
+    base: &Path,
+) -> Manifest {
+    let dir = match &options.assets_dir {
+        Some(dir) if options.assets != Policy::None => dir,
+        _ => return Manifest::new(),
+    };
+    tokio::fs::create_dir_all(dir)
+        .await
+        .unwrap_or_else(|_| panic!("failed to create {}", dir.display()));
+
+    let mut urls = vec![];
+    for block in page.blocks.iter_mut() {
+        collect_urls(block, &mut urls);
+    }
+    collect_meta_urls(meta, &mut urls);
+
+    // documents written to stdout have no directory, so absolute paths are kept
+    let link_dir = if dir.is_absolute() && base.as_os_str().is_empty() {
+        dir.clone()
+    } else {
+        relative_path(dir, base)
+    };
+    let link_dir = link_dir.as_path();
+    let client = Client::new();
+    let limiter = Semaphore::new(options.assets_concurrency as usize);
+    let (client, limiter) = (&client, &limiter);
+    join_all(
+        urls.into_iter()
+            .filter(|(_, (url, internal, _))| options.accepts(url, *internal))
+            .map(|(name, (url, internal, info))| async move {
+                let permit = limiter
+                    .acquire()
+                    .await
+                    .expect("limiter should not be closed");
+                let response = fetch(client, url, options.assets_max_size).await;
+                drop(permit);
+                let (bytes, content_type) = match response {
+                    Ok(Some(response)) => response,
+                    Ok(None) => {
+                        eprintln!("skipped {}: larger than --assets-max-size", url);
+                        return None;
+                    }
+                    Err(e) if !internal => {
+                        eprintln!("skipped {}: {}", url, e);
+                        return None;
+                    }
+                    Err(e) => panic!("failed to download {}: {}", url, e),
+                };
+                let name = format!(
+                    "{}{}",
+                    name,
+                    extension(url, content_type.as_deref(), &bytes)
+                );
+                let path = dir.join(&name);
+                tokio::fs::write(&path, &bytes)
+                    .await
+                    .unwrap_or_else(|_| panic!("failed to write {}", path.display()));
+                let original =
+                    std::mem::replace(url, link_dir.join(&name).to_string_lossy().to_string());
+                if let Some(info) = info {
+                    *info = Some(notion::FileInfo {
+                        size: bytes.len() as u64,
+                        content_type: content_type.clone(),
+                    });
+                }
+                Some((
+                    original,
+                    Asset {
+                        path: path.to_string_lossy().to_string(),
+                        sha256: format!("{:x}", Sha256::digest(&bytes)),
+                        size: bytes.len() as u64,
+                    },
+                ))
+            }),
+    )
+    .await
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// The url of a file, whether it is uploaded to Notion, and where to record
+/// what is known after downloading it.
+type UrlRef<'a> = (
+    &'a mut String,
+    bool,
+    Option<&'a mut Option<notion::FileInfo>>,
+);
+
+fn collect_meta_urls<'a>(meta: &'a mut notion::PageMeta, urls: &mut Vec<(String, UrlRef<'a>)>) {
+    if let Some(cover) = &mut meta.cover {
+        urls.push((format!("{}-cover", meta.id), cover.url_mut()));
+    }
+    match &mut meta.icon {
+        Some(notion::Icon::File { file }) => {
+            urls.push((format!("{}-icon", meta.id), (&mut file.url, true, None)))
+        }
+        Some(notion::Icon::External { external }) => urls.push((
+            format!("{}-icon", meta.id),
+            (&mut external.url, false, None),
+        )),
+        _ => {}
+    }
+}
+
+fn collect_urls<'a>(block: &'a mut notion::Block, urls: &mut Vec<(String, UrlRef<'a>)>) {
+    match &mut block.var {
+        notion::Var::Image { file }
+        | notion::Var::Video { file }
+        | notion::Var::Audio { file }
+        | notion::Var::File { file }
+        | notion::Var::PDF { file } => urls.push((block.id.to_string(), file.url_mut())),
+        notion::Var::Callout { callout } => match &mut callout.icon {
+            notion::Icon::File { file } => {
+                urls.push((format!("{}-icon", block.id), (&mut file.url, true, None)))
+            }
+            notion::Icon::External { external } => urls.push((
+                format!("{}-icon", block.id),
+                (&mut external.url, false, None),
+            )),
+            notion::Icon::Emoji { .. } => {}
+        },
+        _ => {}
+    }
+    if let Some(children) = &mut block.children {
+        for child in children.iter_mut() {
+            collect_urls(child, urls);
+        }
+    }
+}
+
+impl notion::File {
+    fn url_mut(&mut self) -> UrlRef<'_> {
+        match self {
+            notion::File::File { file, info, .. } => (&mut file.url, true, Some(info)),
+            notion::File::External { external, info, .. } => (&mut external.url, false, Some(info)),
+        }
+    }
+}
+
+/// Returns `path` relative to the directory `base`, e.g. `../assets` for
+/// `assets` and `out`, comparing them as absolute paths without `.` and `..`.
+fn relative_path(path: &Path, base: &Path) -> PathBuf {
+    let cwd = std::env::current_dir().expect("failed to get current directory");
+    let path = normalize(&cwd.join(path));
+    let base = normalize(&cwd.join(base));
+    let common = path
+        .components()
+        .zip(base.components())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let mut result = PathBuf::new();
+    for _ in base.components().skip(common) {
+        result.push("..");
+    }
+    result.extend(path.components().skip(common));
+    result
+}
+
+fn normalize(path: &Path) -> PathBuf {
+    let mut result = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                result.pop();
+            }
+            component => result.push(component),
+        }
+    }
+    result
+}
+
+/// Returns the body and the content type, or `None` if the body is larger than `max_size`.
+async fn fetch(
+    client: &Client,
+    url: &str,
+    max_size: Option<u64>,
+) -> Result<Option<(Vec<u8>, Option<String>)>, reqwest::Error> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let too_large = |size: u64| max_size.is_some_and(|max| size > max);
+    if response.content_length().is_some_and(too_large) {
+        return Ok(None);
+    }
+    let content_type = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from);
+    // the body is read in chunks since servers may not send its length
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        bytes.extend_from_slice(&chunk);
+        if too_large(bytes.len() as u64) {
+            return Ok(None);
+        }
+    }
+    Ok(Some((bytes, content_type)))
+}
+
+/// Content types of files and their extensions, the preferred one first.
+const CONTENT_TYPES: &[(&str, &str)] = &[
+    ("image/png", ".png"),
+    ("image/jpeg", ".jpg"),
+    ("image/jpeg", ".jpeg"),
+    ("image/gif", ".gif"),
+    ("image/webp", ".webp"),
+    ("image/svg+xml", ".svg"),
+    ("application/pdf", ".pdf"),
+    ("video/mp4", ".mp4"),
+    ("video/webm", ".webm"),
+    ("video/quicktime", ".mov"),
+    ("audio/mpeg", ".mp3"),
+    ("audio/wav", ".wav"),
+    ("audio/x-wav", ".wav"),
+    ("audio/ogg", ".ogg"),
+    ("application/zip", ".zip"),
+    ("text/plain", ".txt"),
+    ("text/csv", ".csv"),
+];
+
+/// Guesses the content type of a file from the extension of its name or url.
+pub fn content_type(name: &str) -> Option<&'static str> {
+    let path = Url::parse(name)
+        .map(|u| u.path().to_string())
+        .unwrap_or_else(|_| name.to_string());
+    let ext = Path::new(&path)
+        .extension()?
+        .to_string_lossy()
+        .to_lowercase();
+    CONTENT_TYPES
+        .iter()
+        .find(|(_, e)| e[1..] == ext)
+        .map(|(t, _)| *t)
+}
+
+/// Returns the extension of a file with a leading dot, or an empty string if unknown.
+///
+/// The content type is preferred since external urls often lack extensions
+/// (`https://images.example.com/photo?w=800`) or have misleading ones (`.php`).
+/// The magic number is checked last for servers answering `application/octet-stream`.
+fn extension(url: &str, content_type: Option<&str>, bytes: &[u8]) -> String {
+    let from_content_type = content_type.and_then(|c| {
+        let c = c.split(';').next().unwrap_or("").trim();
+        CONTENT_TYPES
+            .iter()
+            .find(|(t, _)| *t == c)
+            .map(|(_, e)| e.to_string())
+    });
+    let from_url = || {
+        Url::parse(url).ok().and_then(|u| {
+            Path::new(u.path())
+                .extension()
+                .map(|e| format!(".{}", e.to_string_lossy()))
+        })
+    };
+    let from_magic = || {
+        let ext = if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            ".png"
+        } else if bytes.starts_with(b"\xff\xd8\xff") {
+            ".jpg"
+        } else if bytes.starts_with(b"GIF8") {
+            ".gif"
+        } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+            ".webp"
+        } else if bytes.starts_with(b"%PDF") {
+            ".pdf"
+        } else {
+            return None;
+        };
+        Some(ext.to_string())
+    };
+    from_content_type
+        .or_else(from_url)
+        .or_else(from_magic)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_extension() {
+        let s3 = "https://s3.us-west-2.amazonaws.com/secure.notion-static.com/0b7c/image.png?X-Amz-Expires=3600";
+        assert_eq!(extension(s3, None, b""), ".png");
+        assert_eq!(extension(s3, Some("application/octet-stream"), b""), ".png");
+        assert_eq!(
+            extension(
+                "https://example.com/photo.php?id=1",
+                Some("image/jpeg"),
+                b""
+            ),
+            ".jpg"
+        );
+        assert_eq!(
+            extension("https://example.com/download", None, b"%PDF-1.7"),
+            ".pdf"
+        );
+        assert_eq!(extension("https://example.com/download", None, b""), "");
+        assert_eq!(extension("not a url", None, b""), "");
+    }
+
+    #[test]
+    fn test_content_type() {
+        assert_eq!(content_type("report.PDF"), Some("application/pdf"));
+        assert_eq!(
+            content_type("https://s3.amazonaws.com/a/photo.jpeg?X-Amz-Expires=3600"),
+            Some("image/jpeg")
+        );
+        assert_eq!(content_type("https://example.com/download"), None);
+        assert_eq!(content_type("archive.tar"), None);
+    }
+
+    #[test]
+    fn test_accepts() {
+        let options = Options {
+            assets_dir: None,
+            assets: Policy::All,
+            assets_allow_domain: vec![],
+            assets_deny_domain: vec!["example.com".to_string()],
+            assets_max_size: None,
+            assets_concurrency: 4,
+            assets_manifest: None,
+        };
+        assert!(options.accepts("https://s3.amazonaws.com/a.png", true));
+        assert!(options.accepts("https://images.unsplash.com/a", false));
+        assert!(!options.accepts("https://cdn.example.com/a.png", false));
+        assert!(!options.accepts("not a url", false));
+
+        let options = Options {
+            assets: Policy::All,
+            assets_allow_domain: vec!["unsplash.com".to_string()],
+            assets_deny_domain: vec![],
+            ..options
+        };
+        assert!(options.accepts("https://images.unsplash.com/a", false));
+        assert!(!options.accepts("https://example.org/a.png", false));
+
+        let options = Options {
+            assets: Policy::Internal,
+            ..options
+        };
+        assert!(options.accepts("https://s3.amazonaws.com/a.png", true));
+        assert!(!options.accepts("https://images.unsplash.com/a", false));
+
+        let options = Options {
+            assets: Policy::None,
+            ..options
+        };
+        assert!(!options.accepts("https://s3.amazonaws.com/a.png", true));
+    }
+
+    #[test]
+    fn test_accepts_mixed_case() {
+        let options = Options {
+            assets_dir: None,
+            assets: Policy::All,
+            assets_allow_domain: vec![],
+            assets_deny_domain: vec![parse_domain(".Example.COM").unwrap()],
+            assets_max_size: None,
+            assets_concurrency: 4,
+            assets_manifest: None,
+        };
+        assert!(!options.accepts("https://CDN.Example.com/a.png", false));
+        assert!(!options.accepts("https://example.com/a.png", false));
+
+        let options = Options {
+            assets_allow_domain: vec![parse_domain("Unsplash.com").unwrap()],
+            assets_deny_domain: vec![],
+            ..options
+        };
+        assert!(options.accepts("https://Images.UNSPLASH.com/a", false));
+        assert!(parse_domain(".").is_err());
+    }
+
+    #[test]
+    fn test_relative_path() {
+        let relative = |path, base| relative_path(Path::new(path), Path::new(base));
+        assert_eq!(relative("./out/assets", "out"), PathBuf::from("assets"));
+        assert_eq!(relative("out/assets", "./out/"), PathBuf::from("assets"));
+        assert_eq!(relative("assets", "out"), PathBuf::from("../assets"));
+        assert_eq!(
+            relative("out/../assets", "out/posts"),
+            PathBuf::from("../../assets")
+        );
+        assert_eq!(relative("assets", ""), PathBuf::from("assets"));
+        assert_eq!(relative("out", "out"), PathBuf::new());
+    }
+
+    #[test]
+    fn test_collect_urls() {
+        let block = |id: u8, kind: &str, body: serde_json::Value| -> notion::Block {
+            let mut block = serde_json::json!({
+                "object": "block",
+                "id": format!("00000000-0000-0000-0000-0000000000{:02x}", id),
+                "archived": false,
+                "has_children": false,
+                "type": kind
+            });
+            block[kind] = body;
+            serde_json::from_value(block).unwrap()
+        };
+        let uploaded = |url: &str| {
+            serde_json::json!({
+                "caption": [],
+                "type": "file",
+                "file": {"url": url, "expiry_time": "2023-10-01T00:00:00.000Z"}
+            })
+        };
+        let external = |url: &str| serde_json::json!({"caption": [], "type": "external", "external": {"url": url}});
+
+        let mut callout = block(
+            5,
+            "callout",
+            serde_json::json!({
+                "rich_text": [],
+                "icon": {"type": "file", "file": {"url": "https://s3.example/icon.svg", "expiry_time": "2023-10-01T00:00:00.000Z"}},
+                "color": "default"
+            }),
+        );
+        callout.children = Some(vec![block(6, "pdf", external("https://example.com/a.pdf"))]);
+        let mut toggle = block(
+            7,
+            "toggle",
+            serde_json::json!({"rich_text": [], "color": "default"}),
+        );
+        toggle.children = Some(vec![block(
+            8,
+            "image",
+            uploaded("https://s3.example/b.png"),
+        )]);
+        let mut blocks = [
+            block(1, "image", uploaded("https://s3.example/a.png")),
+            block(2, "file", uploaded("https://s3.example/a.zip")),
+            block(3, "video", external("https://example.com/a.mp4")),
+            block(4, "audio", uploaded("https://s3.example/a.mp3")),
+            callout,
+            toggle,
+            block(
+                9,
+                "paragraph",
+                serde_json::json!({"rich_text": [], "color": "default"}),
+            ),
+        ];
+        let mut meta: notion::PageMeta = serde_json::from_value(serde_json::json!({
+            "id": "00000000-0000-0000-0000-0000000000ff",
+            "archived": false,
+            "created_time": "2022-03-01T19:05:00.000Z",
+            "last_edited_time": "2022-07-06T20:25:00.000Z",
+            "created_by": {"object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4"},
+            "last_edited_by": {"object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4"},
+            "icon": {"type": "external", "external": {"url": "https://example.com/icon.png"}},
+            "cover": uploaded("https://s3.example/cover.jpg"),
+            "parent": {"type": "workspace", "workspace": true},
+            "properties": {}
+        }))
+        .unwrap();
+
+        let mut urls = vec![];
+        for block in blocks.iter_mut() {
+            collect_urls(block, &mut urls);
+        }
+        collect_meta_urls(&mut meta, &mut urls);
+        let mut collected = vec![];
+        for (name, (url, internal, info)) in urls {
+            collected.push((name.clone(), url.clone(), internal, info.is_some()));
+            *url = format!("assets/{}", name);
+            if let Some(info) = info {
+                *info = Some(notion::FileInfo {
+                    size: 1,
+                    content_type: None,
+                });
+            }
+        }
+        let id = |id: u8| format!("00000000-0000-0000-0000-0000000000{:02x}", id);
+        let expect = [
+            (id(1), "https://s3.example/a.png", true, true),
+            (id(2), "https://s3.example/a.zip", true, true),
+            (id(3), "https://example.com/a.mp4", false, true),
+            (id(4), "https://s3.example/a.mp3", true, true),
+            (
+                format!("{}-icon", id(5)),
+                "https://s3.example/icon.svg",
+                true,
+                false,
+            ),
+            (id(6), "https://example.com/a.pdf", false, true),
+            (id(8), "https://s3.example/b.png", true, true),
+            (
+                format!("{}-cover", id(255)),
+                "https://s3.example/cover.jpg",
+                true,
+                true,
+            ),
+            (
+                format!("{}-icon", id(255)),
+                "https://example.com/icon.png",
+                false,
+                false,
+            ),
+        ]
+        .map(|(name, url, internal, info)| (name, url.to_string(), internal, info));
+        assert_eq!(collected, expect);
+
+        // the urls and infos are those of the blocks and the page
+        let file = |block: &notion::Block| match &block.var {
+            notion::Var::Image { file }
+            | notion::Var::Video { file }
+            | notion::Var::Audio { file }
+            | notion::Var::File { file }
+            | notion::Var::PDF { file } => match file {
+                notion::File::File { file, info, .. } => (file.url.clone(), info.is_some()),
+                notion::File::External { external, info, .. } => {
+                    (external.url.clone(), info.is_some())
+                }
+            },
+            _ => panic!("not a file block"),
+        };
+        for block in &blocks[..4] {
+            assert_eq!(file(block), (format!("assets/{}", block.id), true));
+        }
+        match &blocks[4].var {
+            notion::Var::Callout { callout } => assert_eq!(
+                callout.icon,
+                notion::Icon::File {
+                    file: notion::FileLink {
+                        url: format!("assets/{}-icon", id(5)),
+                        expiry_time: "2023-10-01T00:00:00.000Z".parse().unwrap(),
+                    }
+                }
+            ),
+            _ => panic!("not a callout"),
+        }
+        let nested = [&blocks[4], &blocks[5]].map(|b| &b.children.as_ref().unwrap()[0]);
+        assert_eq!(file(nested[0]), (format!("assets/{}", id(6)), true));
+        assert_eq!(file(nested[1]), (format!("assets/{}", id(8)), true));
+        match &meta.cover {
+            Some(notion::File::File { file, info, .. }) => {
+                assert_eq!(file.url, format!("assets/{}-cover", id(255)));
+                assert!(info.is_some());
+            }
+            _ => panic!("cover should be uploaded"),
+        }
+        assert_eq!(
+            meta.icon,
+            Some(notion::Icon::External {
+                external: notion::Link {
+                    url: format!("assets/{}-icon", id(255)),
+                }
+            })
+        );
+    }
+}
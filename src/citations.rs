@@ -0,0 +1,210 @@
+use crate::pandoc::{self, Block, Citation, CitationMode, Inline};
+
+/// Turns citations written as in Pandoc's Markdown into `Cite`s for `--citeproc`:
+/// `[@smith2020]`, `[see @smith2020, p. 3; -@doe2021]` and `@smith2020` in text.
+pub fn parse(blocks: &mut [Block]) {
+    pandoc::walk_inline_lists(blocks, &mut |inlines| {
+        if inlines
+            .iter()
+            .any(|i| matches!(i, Inline::Str(s) if s.contains('@')))
+        {
+            *inlines = std::mem::take(inlines)
+                .into_iter()
+                .flat_map(|inline| match inline {
+                    Inline::Str(s) => split_citations(&s),
+                    inline => vec![inline],
+                })
+                .collect();
+        }
+    });
+}
+
+/// Splits `s` into strings and citations.
+fn split_citations(s: &str) -> Vec<Inline> {
+    let mut result = vec![];
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find(['[', '@']) {
+        let (before, candidate) = rest.split_at(start);
+        let cite = if candidate.starts_with('[') {
+            candidate.find(']').and_then(|end| {
+                let citations = bracketed(&candidate[1..end])?;
+                Some((citations, end + 1))
+            })
+        } else {
+            let preceding = before.chars().last().or_else(|| text.chars().last());
+            let in_word = preceding.is_some_and(|c| c.is_alphanumeric());
+            match key(&candidate[1..]) {
+                (key, _) if !key.is_empty() && !in_word => Some((
+                    vec![citation(key, "", "", CitationMode::AuthorInText)],
+                    key.len() + 1,
+                )),
+                _ => None,
+            }
+        };
+        text.push_str(before);
+        match cite {
+            Some((citations, len)) => {
+                if !text.is_empty() {
+                    result.push(Inline::Str(std::mem::take(&mut text)));
+                }
+                result.push(Inline::Cite(
+                    citations,
+                    vec![Inline::Str(candidate[..len].to_string())],
+                ));
+                rest = &candidate[len..];
+            }
+            None => {
+                text.push_str(&candidate[..1]);
+                rest = &candidate[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        result.push(Inline::Str(text));
+    }
+    result
+}
+
+/// Parses the inside of `[...]`, which is citations separated by `;`.
+fn bracketed(s: &str) -> Option<Vec<Citation>> {
+    s.split(';')
+        .map(|part| {
+            let at = part.find('@')?;
+            let (prefix, mode) = match part[..at].strip_suffix('-') {
+                Some(prefix) => (prefix, CitationMode::SuppressAuthor),
+                None => (&part[..at], CitationMode::NormalCitation),
+            };
+            // `@` and `-@` start a word, unlike in `me@example.com`
+            if prefix.chars().last().is_some_and(|c| c.is_alphanumeric()) {
+                return None;
+            }
+            match key(&part[at + 1..]) {
+                ("", _) => None,
+                (key, suffix) => Some(citation(key, prefix.trim(), suffix.trim_end(), mode)),
+            }
+        })
+        .collect()
+}
+
+/// Splits `s` into a citation key and the rest. Punctuation such as `:` and
+/// `.` belongs to the key only when followed by a letter or a digit, so that
+/// `@smith2020.` ends the sentence.
+fn key(s: &str) -> (&str, &str) {
+    let is_key_char = |c: char| c.is_alphanumeric() || c == '_';
+    let chars: Vec<(usize, char)> = s.char_indices().collect();
+    let mut end = 0;
+    for (i, &(pos, c)) in chars.iter().enumerate() {
+        let inner = ":.#$%&-+?<>~/".contains(c)
+            && end > 0
+            && chars.get(i + 1).is_some_and(|&(_, n)| is_key_char(n));
+        if !is_key_char(c) && !inner {
+            break;
+        }
+        end = pos + c.len_utf8();
+    }
+    s.split_at(end)
+}
+
+fn citation(key: &str, prefix: &str, suffix: &str, mode: CitationMode) -> Citation {
+    Citation {
+        citation_id: key.to_string(),
+        citation_prefix: words(prefix),
+        citation_suffix: words(suffix),
+        citation_mode: mode,
+        citation_note_num: 0,
+        citation_hash: 0,
+    }
+}
+
+/// Returns `s` as `Str`s separated by `Space`s, keeping leading and trailing spaces.
+fn words(s: &str) -> Vec<Inline> {
+    let mut inlines = vec![];
+    for (i, word) in s.split(' ').enumerate() {
+        if i > 0 && !matches!(inlines.last(), Some(Inline::Space)) {
+            inlines.push(Inline::Space);
+        }
+        if !word.is_empty() {
+            inlines.push(Inline::Str(word.to_string()));
+        }
+    }
+    inlines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_citations() {
+        assert_eq!(
+            serde_json::to_value(split_citations(
+                "As @smith2020. said [see @doe:2021, p. 3; -@roe]"
+            ))
+            .unwrap(),
+            serde_json::json!([
+                {"t": "Str", "c": "As "},
+                {"t": "Cite", "c": [
+                    [{
+                        "citationId": "smith2020",
+                        "citationPrefix": [],
+                        "citationSuffix": [],
+                        "citationMode": {"t": "AuthorInText"},
+                        "citationNoteNum": 0,
+                        "citationHash": 0
+                    }],
+                    [{"t": "Str", "c": "@smith2020"}]
+                ]},
+                {"t": "Str", "c": ". said "},
+                {"t": "Cite", "c": [
+                    [
+                        {
+                            "citationId": "doe:2021",
+                            "citationPrefix": [{"t": "Str", "c": "see"}],
+                            "citationSuffix": [
+                                {"t": "Str", "c": ","},
+                                {"t": "Space"},
+                                {"t": "Str", "c": "p."},
+                                {"t": "Space"},
+                                {"t": "Str", "c": "3"}
+                            ],
+                            "citationMode": {"t": "NormalCitation"},
+                            "citationNoteNum": 0,
+                            "citationHash": 0
+                        },
+                        {
+                            "citationId": "roe",
+                            "citationPrefix": [],
+                            "citationSuffix": [],
+                            "citationMode": {"t": "SuppressAuthor"},
+                            "citationNoteNum": 0,
+                            "citationHash": 0
+                        }
+                    ],
+                    [{"t": "Str", "c": "[see @doe:2021, p. 3; -@roe]"}]
+                ]}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_not_citations() {
+        for s in [
+            "mail me at me@example.com",
+            "[a link] and @",
+            "[not a citation]",
+            "price @ 3",
+            "price @ 3, mail me@example.com",
+            "see [1] and foo@bar.org",
+            "[contact me@example.com]",
+        ] {
+            assert_eq!(
+                serde_json::to_value(split_citations(s)).unwrap(),
+                serde_json::json!([{"t": "Str", "c": s}]),
+                "{}",
+                s
+            );
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use reqwest::Url;
+
+use crate::escape_html;
+
+/// An embedded page of a known provider.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Embed {
+    pub provider: &'static str,
+    /// Attributes identifying the content, e.g. `video-id`
+    pub attrs: Vec<(String, String)>,
+    /// HTML embedding the content as the provider recommends
+    pub html: String,
+}
+
+/// Recognizes the url of a provider. Add a function here to support another provider.
+type Resolver = fn(&Url) -> Option<Embed>;
+
+const RESOLVERS: &[Resolver] = &[youtube, vimeo, gist, twitter, figma, google_docs];
+
+/// Returns the embed of `url` if it is a page of a known provider.
+pub fn resolve(url: &str) -> Option<Embed> {
+    let url = Url::parse(url).ok()?;
+    RESOLVERS.iter().find_map(|resolve| resolve(&url))
+}
+
+/// Returns the host of `url` without `www.`.
+fn host(url: &Url) -> &str {
+    let host = url.host_str().unwrap_or("");
+    host.strip_prefix("www.").unwrap_or(host)
+}
+
+fn segments(url: &Url) -> Vec<&str> {
+    url.path_segments()
+        .map(|s| s.filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default()
+}
+
+fn iframe(src: &str) -> String {
+    format!(
+        r#"<iframe src="{}" frameborder="0" allowfullscreen></iframe>"#,
+        escape_html(src)
+    )
+}
+
+fn youtube(url: &Url) -> Option<Embed> {
+    let id = match (host(url), segments(url).as_slice()) {
+        ("youtu.be", [id]) => id.to_string(),
+        ("youtube.com" | "m.youtube.com", ["watch"]) => url
+            .query_pairs()
+            .find(|(k, _)| k == "v")
+            .map(|(_, v)| v.to_string())?,
+        ("youtube.com" | "m.youtube.com", ["embed" | "shorts" | "live", id]) => id.to_string(),
+        _ => return None,
+    };
+    Some(Embed {
+        provider: "youtube",
+        html: iframe(&format!("https://www.youtube.com/embed/{}", id)),
+        attrs: vec![("video-id".to_string(), id)],
+    })
+}
+
+fn vimeo(url: &Url) -> Option<Embed> {
+    let id = match (host(url), segments(url).as_slice()) {
+        ("vimeo.com", [id]) | ("player.vimeo.com", ["video", id]) => id.to_string(),
+        _ => return None,
+    };
+    if !id.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some(Embed {
+        provider: "vimeo",
+        html: iframe(&format!("https://player.vimeo.com/video/{}", id)),
+        attrs: vec![("video-id".to_string(), id)],
+    })
+}
+
+fn gist(url: &Url) -> Option<Embed> {
+    let (user, id) = match (host(url), segments(url).as_slice()) {
+        ("gist.github.com", [user, id]) => (user.to_string(), id.to_string()),
+        _ => return None,
+    };
+    Some(Embed {
+        provider: "gist",
+        html: format!(
+            r#"<script src="https://gist.github.com/{}/{}.js"></script>"#,
+            escape_html(&user),
+            escape_html(&id)
+        ),
+        attrs: vec![("gist-id".to_string(), id), ("user".to_string(), user)],
+    })
+}
+
+fn twitter(url: &Url) -> Option<Embed> {
+    let (user, id) = match (host(url), segments(url).as_slice()) {
+        ("twitter.com" | "x.com", [user, "status", id, ..]) => (user.to_string(), id.to_string()),
+        _ => return None,
+    };
+    Some(Embed {
+        provider: "twitter",
+        html: format!(
+            concat!(
+                r#"<blockquote class="twitter-tweet"><a href="https://twitter.com/{}/status/{}"></a></blockquote>"#,
+                r#"<script async src="https://platform.twitter.com/widgets.js"></script>"#
+            ),
+            escape_html(&user),
+            escape_html(&id)
+        ),
+        attrs: vec![("tweet-id".to_string(), id), ("user".to_string(), user)],
+    })
+}
+
+fn figma(url: &Url) -> Option<Embed> {
+    match (host(url), segments(url).as_slice()) {
+        ("figma.com", ["file" | "design" | "proto" | "board", ..]) => {}
+        _ => return None,
+    }
+    let src = Url::parse_with_params(
+        "https://www.figma.com/embed",
+        &[("embed_host", "share"), ("url", url.as_str())],
+    )
+    .ok()?;
+    Some(Embed {
+        provider: "figma",
+        html: iframe(src.as_str()),
+        attrs: vec![],
+    })
+}
+
+fn google_docs(url: &Url) -> Option<Embed> {
+    let (kind, id) = match (host(url), segments(url).as_slice()) {
+        (
+            "docs.google.com",
+            [kind @ ("document" | "spreadsheets" | "presentation"), "d", id, ..],
+        ) => (kind.to_string(), id.to_string()),
+        _ => return None,
+    };
+    Some(Embed {
+        provider: "google-docs",
+        html: iframe(&format!(
+            "https://docs.google.com/{}/d/{}/preview",
+            kind, id
+        )),
+        attrs: vec![("doc-type".to_string(), kind), ("doc-id".to_string(), id)],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn attrs(url: &str) -> Option<(&'static str, Vec<(String, String)>)> {
+        resolve(url).map(|e| (e.provider, e.attrs))
+    }
+
+    #[test]
+    fn test_resolve() {
+        let video_id =
+            |provider, id: &str| Some((provider, vec![("video-id".to_string(), id.to_string())]));
+        assert_eq!(
+            attrs("https://www.youtube.com/watch?v=dQw4w9WgXcQ&t=42"),
+            video_id("youtube", "dQw4w9WgXcQ")
+        );
+        assert_eq!(
+            attrs("https://youtu.be/dQw4w9WgXcQ"),
+            video_id("youtube", "dQw4w9WgXcQ")
+        );
+        assert_eq!(
+            attrs("https://vimeo.com/76979871"),
+            video_id("vimeo", "76979871")
+        );
+        assert_eq!(attrs("https://vimeo.com/channels"), None);
+        assert_eq!(
+            attrs("https://x.com/rustlang/status/1712345678901234567"),
+            Some((
+                "twitter",
+                vec![
+                    ("tweet-id".to_string(), "1712345678901234567".to_string()),
+                    ("user".to_string(), "rustlang".to_string())
+                ]
+            ))
+        );
+        assert_eq!(
+            attrs("https://docs.google.com/spreadsheets/d/1AbC/edit#gid=0"),
+            Some((
+                "google-docs",
+                vec![
+                    ("doc-type".to_string(), "spreadsheets".to_string()),
+                    ("doc-id".to_string(), "1AbC".to_string())
+                ]
+            ))
+        );
+        assert_eq!(attrs("https://example.com/watch?v=1"), None);
+        assert_eq!(attrs("not a url"), None);
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            resolve("https://gist.github.com/octocat/6cad326836d38bd3a7ae")
+                .unwrap()
+                .html,
+            r#"<script src="https://gist.github.com/octocat/6cad326836d38bd3a7ae.js"></script>"#
+        );
+        assert_eq!(
+            resolve("https://www.figma.com/file/abc/Design?node-id=1")
+                .unwrap()
+                .html,
+            r#"<iframe src="https://www.figma.com/embed?embed_host=share&amp;url=https%3A%2F%2Fwww.figma.com%2Ffile%2Fabc%2FDesign%3Fnode-id%3D1" frameborder="0" allowfullscreen></iframe>"#
+        );
+    }
+}
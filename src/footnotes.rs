@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use crate::pandoc::{self, Block, Inline};
+
+/// Turns `[^label]` markers into notes defined in the section titled `title`,
+/// which is removed from `blocks`.
+///
+/// The section is either a heading followed by the definitions, up to the
+/// next heading of the same or a higher level, or a toggle containing them.
+/// Definitions are paragraphs or list items starting with `[^label]:`;
+/// the following blocks without markers continue the last definition.
+/// Markers without definitions are kept as text.
+pub fn attach(blocks: &mut Vec<Block>, title: &str) {
+    let section = match take_section(blocks, title) {
+        Some(section) => section,
+        None => return,
+    };
+    let notes = definitions(section);
+    if notes.is_empty() {
+        return;
+    }
+    pandoc::walk_inline_lists(blocks, &mut |inlines| {
+        if inlines
+            .iter()
+            .any(|i| matches!(i, Inline::Str(s) if s.contains("[^")))
+        {
+            *inlines = std::mem::take(inlines)
+                .into_iter()
+                .flat_map(|inline| match inline {
+                    Inline::Str(s) => split_markers(&s, &notes),
+                    inline => vec![inline],
+                })
+                .collect();
+        }
+    });
+}
+
+/// Removes the section titled `title` from `blocks` and returns its contents.
+fn take_section(blocks: &mut Vec<Block>, title: &str) -> Option<Vec<Block>> {
+    let is_title = |inlines: &[Inline]| pandoc::stringify(inlines).trim() == title;
+    for i in 0..blocks.len() {
+        match &mut blocks[i] {
+            Block::Header(level, _, inlines) if is_title(inlines) => {
+                let level = *level;
+                let end = blocks[i + 1..]
+                    .iter()
+                    .position(|b| matches!(b, Block::Header(l, ..) if *l <= level))
+                    .map_or(blocks.len(), |e| i + 1 + e);
+                let mut section: Vec<Block> = blocks.drain(i..end).collect();
+                section.remove(0);
+                return Some(section);
+            }
+            Block::BulletList(items) => {
+                let toggle = items.iter().position(
+                    |item| matches!(item.first(), Some(Block::Plain(inlines)) if is_title(inlines)),
+                );
+                if let Some(j) = toggle {
+                    let mut section = items.remove(j);
+                    if items.is_empty() {
+                        blocks.remove(i);
+                    }
+                    section.remove(0);
+                    return Some(section);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Returns the contents of the definitions in `section` by label.
+fn definitions(section: Vec<Block>) -> HashMap<String, Vec<Block>> {
+    let blocks = section.into_iter().flat_map(|block| match block {
+        Block::BulletList(items) | Block::OrderedList(_, items) => {
+            items.into_iter().flatten().collect()
+        }
+        block => vec![block],
+    });
+    let mut notes: HashMap<String, Vec<Block>> = HashMap::new();
+    let mut last = None;
+    for mut block in blocks {
+        let label = match &mut block {
+            Block::Plain(inlines) | Block::Para(inlines) => strip_definition(inlines),
+            _ => None,
+        };
+        // a note of one paragraph, as Pandoc's Markdown makes
+        if let Block::Plain(inlines) = block {
+            block = Block::Para(inlines);
+        }
+        match label {
+            Some(label) => {
+                notes.insert(label.clone(), vec![block]);
+                last = Some(label);
+            }
+            None => {
+                if let Some(note) = last.as_ref().and_then(|l| notes.get_mut(l)) {
+                    note.push(block);
+                }
+            }
+        }
+    }
+    notes
+}
+
+/// Removes a leading `[^label]:` from `inlines` and returns the label.
+fn strip_definition(inlines: &mut Vec<Inline>) -> Option<String> {
+    let s = match inlines.first_mut() {
+        Some(Inline::Str(s)) => s,
+        _ => return None,
+    };
+    let (label, rest) = s.strip_prefix("[^")?.split_once("]:")?;
+    if !is_label(label) {
+        return None;
+    }
+    let label = label.to_string();
+    let rest = rest.trim_start().to_string();
+    if rest.is_empty() {
+        inlines.remove(0);
+        if matches!(inlines.first(), Some(Inline::Space)) {
+            inlines.remove(0);
+        }
+    } else {
+        *s = rest;
+    }
+    Some(label)
+}
+
+fn is_label(label: &str) -> bool {
+    !label.is_empty() && !label.contains(|c: char| c.is_whitespace() || c == '^' || c == ']')
+}
+
+/// Splits `s` at markers of `notes` into strings and notes.
+fn split_markers(s: &str, notes: &HashMap<String, Vec<Block>>) -> Vec<Inline> {
+    let mut result = vec![];
+    let mut text = String::new();
+    let mut rest = s;
+    while let Some(start) = rest.find("[^") {
+        let note = rest[start + 2..]
+            .split_once(']')
+            .filter(|(label, _)| is_label(label))
+            .and_then(|(label, after)| notes.get(label).map(|note| (note, after)));
+        match note {
+            Some((note, after)) => {
+                text.push_str(&rest[..start]);
+                if !text.is_empty() {
+                    result.push(Inline::Str(std::mem::take(&mut text)));
+                }
+                result.push(Inline::Note(note.clone()));
+                rest = after;
+            }
+            None => {
+                text.push_str(&rest[..start + 2]);
+                rest = &rest[start + 2..];
+            }
+        }
+    }
+    text.push_str(rest);
+    if !text.is_empty() {
+        result.push(Inline::Str(text));
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn para(s: &str) -> Block {
+        Block::Para(vec![Inline::Str(s.to_string())])
+    }
+
+    #[test]
+    fn test_attach() {
+        let mut blocks = vec![
+            para("Rust[^1] and Go[^go], not [^x]."),
+            Block::Header(
+                2,
+                pandoc::Attr::default(),
+                vec![Inline::Str("Footnotes".to_string())],
+            ),
+            para("[^1]: A language."),
+            para("Second paragraph."),
+            Block::BulletList(vec![vec![para("[^go]: Another.")]]),
+            Block::Header(
+                2,
+                pandoc::Attr::default(),
+                vec![Inline::Str("After".to_string())],
+            ),
+        ];
+        attach(&mut blocks, "Footnotes");
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap(),
+            serde_json::json!([
+                {"t": "Para", "c": [
+                    {"t": "Str", "c": "Rust"},
+                    {"t": "Note", "c": [
+                        {"t": "Para", "c": [{"t": "Str", "c": "A language."}]},
+                        {"t": "Para", "c": [{"t": "Str", "c": "Second paragraph."}]}
+                    ]},
+                    {"t": "Str", "c": " and Go"},
+                    {"t": "Note", "c": [{"t": "Para", "c": [{"t": "Str", "c": "Another."}]}]},
+                    {"t": "Str", "c": ", not [^x]."}
+                ]},
+                {"t": "Header", "c": [2, ["", [], []], [{"t": "Str", "c": "After"}]]}
+            ])
+        );
+    }
+
+    #[test]
+    fn test_attach_toggle() {
+        let mut blocks = vec![
+            para("Rust[^1]"),
+            Block::BulletList(vec![vec![
+                Block::Plain(vec![Inline::Str("Footnotes".to_string())]),
+                Block::Para(vec![
+                    Inline::Str("[^1]:".to_string()),
+                    Inline::Space,
+                    Inline::Str("A language.".to_string()),
+                ]),
+            ]]),
+        ];
+        attach(&mut blocks, "Footnotes");
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap(),
+            serde_json::json!([
+                {"t": "Para", "c": [
+                    {"t": "Str", "c": "Rust"},
+                    {"t": "Note", "c": [{"t": "Para", "c": [{"t": "Str", "c": "A language."}]}]}
+                ]}
+            ])
+        );
+    }
+}
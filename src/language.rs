@@ -0,0 +1,110 @@
+/// Returns the class of a code block in `language` of Notion, named as
+/// skylighting (the highlighter of Pandoc) does, or `None` for plain text.
+///
+/// https://developers.notion.com/reference/block#code
+pub fn class(language: &str) -> Option<String> {
+    let class = match language {
+        "abap" => "abap",
+        "agda" => "agda",
+        "arduino" => "cpp",
+        "assembly" => "gnuassembler",
+        "bash" => "bash",
+        "basic" => "basic",
+        "bnf" => "bnf",
+        "c" => "c",
+        "c#" => "cs",
+        "c++" => "cpp",
+        "clojure" => "clojure",
+        "coffeescript" => "coffee",
+        "coq" => "coq",
+        "css" => "css",
+        "dart" => "dart",
+        "dhall" => "dhall",
+        "diff" => "diff",
+        "docker" => "dockerfile",
+        "ebnf" => "ebnf",
+        "elixir" => "elixir",
+        "elm" => "elm",
+        "erlang" => "erlang",
+        "f#" => "fsharp",
+        "flow" => "javascript",
+        "fortran" => "fortranfree",
+        "gherkin" => "gherkin",
+        "glsl" => "glsl",
+        "go" => "go",
+        "graphql" => "graphql",
+        "groovy" => "groovy",
+        "haskell" => "haskell",
+        "html" => "html",
+        "idris" => "idris",
+        "java" => "java",
+        "javascript" => "javascript",
+        "json" => "json",
+        "julia" => "julia",
+        "kotlin" => "kotlin",
+        "latex" => "latex",
+        "less" => "less",
+        "lisp" => "commonlisp",
+        "livescript" => "livescript",
+        "llvm ir" => "llvm",
+        "lua" => "lua",
+        "makefile" => "makefile",
+        "markdown" => "markdown",
+        "markup" => "xml",
+        "matlab" => "matlab",
+        "mathematica" => "mathematica",
+        "mermaid" => "mermaid",
+        "nix" => "nix",
+        "objective-c" => "objectivec",
+        "ocaml" => "ocaml",
+        "pascal" => "pascal",
+        "perl" => "perl",
+        "php" => "php",
+        "plain text" => return None,
+        "powershell" => "powershell",
+        "prolog" => "prolog",
+        "protobuf" => "protobuf",
+        "purescript" => "purescript",
+        "python" => "python",
+        "r" => "r",
+        "racket" => "racket",
+        "reason" => "reason",
+        "ruby" => "ruby",
+        "rust" => "rust",
+        "sass" => "sass",
+        "scala" => "scala",
+        "scheme" => "scheme",
+        "scss" => "scss",
+        "shell" => "bash",
+        "solidity" => "solidity",
+        "sql" => "sql",
+        "swift" => "swift",
+        "toml" => "toml",
+        "typescript" => "typescript",
+        "vb.net" => "vbnet",
+        "verilog" => "verilog",
+        "vhdl" => "vhdl",
+        "visual basic" => "monobasic",
+        "webassembly" => "wasm",
+        "xml" => "xml",
+        "yaml" => "yaml",
+        "java/c/c++/c#" => "java",
+        // languages added to Notion after this table
+        _ => return Some(language.replace(' ', "-")),
+    };
+    Some(class.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_class() {
+        assert_eq!(class("rust"), Some("rust".to_string()));
+        assert_eq!(class("c++"), Some("cpp".to_string()));
+        assert_eq!(class("shell"), Some("bash".to_string()));
+        assert_eq!(class("plain text"), None);
+        assert_eq!(class("new language"), Some("new-language".to_string()));
+    }
+}
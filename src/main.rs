@@ -1,10 +1,19 @@
+mod assets;
+mod citations;
+mod embed;
+mod footnotes;
+mod language;
 mod notion;
 mod pandoc;
+mod preview;
 
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use clap::Parser;
-use itertools::join;
+use chrono::format::{Item, StrftimeItems};
+use chrono::{DateTime, FixedOffset, Local, Offset};
+use clap::{CommandFactory, ErrorKind, Parser, Subcommand};
+use itertools::{join, Itertools};
 use uuid::Uuid;
 extern crate openssl_probe;
 
@@ -12,80 +21,867 @@ extern crate openssl_probe;
 #[clap(author, version, about, long_about = None)]
 struct Args {
     #[clap(short = 'i')]
-    id: String,
+    id: Option<String>,
     #[clap(short = 's')]
     secret: String,
+    #[clap(flatten)]
+    options: Options,
+    #[clap(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Args)]
+struct Options {
+    /// Time zone of dates in metadata (e.g. Asia/Tokyo) [default: local time zone]
+    #[clap(long, global = true)]
+    timezone: Option<chrono_tz::Tz>,
+    /// strftime format of `date` and `lastmod` in metadata
+    #[clap(long, global = true, default_value = "%Y-%m-%d", value_parser = parse_date_format)]
+    date_format: String,
+    /// How to emit captions of code blocks
+    #[clap(long, global = true, value_enum, default_value = "attribute")]
+    code_caption: CodeCaption,
+    /// Use paragraphs starting with this prefix (e.g. "Table: ") right before tables as their captions
+    #[clap(long, global = true)]
+    table_caption_prefix: Option<String>,
+    /// How to emit Mermaid, Graphviz and PlantUML code blocks
+    #[clap(long, global = true, value_enum, default_value = "none")]
+    diagram: Diagram,
+    /// Class of code blocks in a Notion language, overriding the built-in table
+    /// (e.g. `--language "plain text=text"`; empty CLASS for no class)
+    #[clap(long, global = true, value_name = "NOTION=CLASS", value_parser = parse_key_value)]
+    language: Vec<(String, String)>,
+    /// How to set ids of headings, callouts, code blocks, figures and tables
+    #[clap(long, global = true, value_enum, default_value = "none")]
+    block_ids: BlockIds,
+    /// Build a table of contents where the table_of_contents block is
+    /// instead of setting `toc` in metadata
+    #[clap(long, global = true)]
+    toc_block: bool,
+    /// Add admonition classes such as `tip` to callouts with icons like 💡
+    #[clap(long, global = true, value_enum, default_value = "none")]
+    admonition: Admonition,
+    /// Admonition class of callouts with an emoji icon, overriding the built-in table
+    /// (e.g. `--admonition-icon "🔥=danger"`)
+    #[clap(long, global = true, value_name = "EMOJI=CLASS", value_parser = parse_key_value)]
+    admonition_icon: Vec<(String, String)>,
+    /// Emit audio blocks as raw HTML `<audio>` elements instead of links
+    #[clap(long, global = true)]
+    audio_html: bool,
+    /// Emit embeds of known providers (YouTube, Vimeo, Gist, Twitter, Figma, Google Docs)
+    /// as raw HTML followed by the links
+    #[clap(long, global = true)]
+    embed_html: bool,
+    /// Emit html and latex code blocks captioned with this marker (e.g. "raw") as raw content
+    #[clap(long, global = true)]
+    raw_caption: Option<String>,
+    /// Emit inline code ending with `{=FORMAT}`, e.g. `<kbd>Ctrl</kbd>{=html}`, as raw content
+    #[clap(long, global = true)]
+    raw_inline: bool,
+    /// Turn `[^1]` markers into footnotes defined under the heading or toggle titled
+    /// this (e.g. "Footnotes"), removing the section
+    #[clap(long, global = true, value_name = "TITLE")]
+    footnotes: Option<String>,
+    /// Turn `[@key]` and `@key` in text into citations for `pandoc --citeproc`
+    #[clap(long, global = true)]
+    citations: bool,
+    /// How to emit comments on blocks and the page, fetching them needs the
+    /// "Read comments" capability of the integration
+    #[clap(long, global = true, value_enum, default_value = "none")]
+    comments: Comments,
+    /// Maximum number of concurrent requests for comments
+    #[clap(long, global = true, default_value = "3", value_parser = clap::value_parser!(u64).range(1..))]
+    comments_concurrency: u64,
+    #[clap(flatten)]
+    preview: preview::Options,
+    #[clap(flatten)]
+    assets: assets::Options,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Diagram {
+    /// Plain code blocks
+    None,
+    /// Code blocks with classes of the engine and `diagram`, captioned by a `caption` attribute
+    Class,
+    /// Figures containing the code blocks, captioned by the caption of the code blocks
+    Figure,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum BlockIds {
+    /// Ids only on figures and tables, leaving the rest to Pandoc
+    None,
+    /// Notion block ids
+    Id,
+    /// Slugs of the text for headings and Notion block ids for the rest,
+    /// with Notion block ids in `data-notion-id` attributes
+    Slug,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Admonition {
+    /// Only the class `callout`
+    None,
+    /// The class `callout` and the admonition class, e.g. `tip`
+    Class,
+    /// The class of Quarto callouts instead of `callout`, e.g. `callout-tip`
+    Quarto,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum Comments {
+    /// Do not fetch comments
+    None,
+    /// Notes at the end of the commented blocks, prefixed by the authors
+    Note,
+    /// Spans with class `comment` and `author` and `time` attributes
+    Annotation,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+enum CodeCaption {
+    /// `filename` attribute if the caption looks like a file name, `caption` attribute otherwise
+    Attribute,
+    /// Figure containing the code block
+    Figure,
+    /// Div with class `code` containing a Div with class `caption` and the code block
+    Div,
+}
+
+fn parse_date_format(s: &str) -> Result<String, String> {
+    if StrftimeItems::new(s).any(|item| item == Item::Error) {
+        return Err(format!("invalid strftime format: {}", s));
+    }
+    Ok(s.to_string())
+}
+
+fn parse_key_value(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .ok_or_else(|| format!("no `=` in `{}`", s))
+}
+
+impl Options {
+    fn language_class(&self, language: &str) -> Option<String> {
+        match self.language.iter().rev().find(|(k, _)| k == language) {
+            Some((_, class)) if class.is_empty() => None,
+            Some((_, class)) => Some(class.clone()),
+            None => language::class(language),
+        }
+    }
+
+    /// Returns the admonition class of a callout with `icon`.
+    fn admonition_class(&self, icon: &notion::Icon) -> Option<String> {
+        let emoji = match icon {
+            notion::Icon::Emoji { emoji } => emoji,
+            _ => return None,
+        };
+        if let Some((_, class)) = self.admonition_icon.iter().rev().find(|(k, _)| k == emoji) {
+            return Some(class.clone());
+        }
+        // the classes of Quarto callouts, which GitHub alerts and MkDocs admonitions also have
+        let class = match emoji.trim_end_matches('\u{fe0f}') {
+            "💡" => "tip",
+            "⚠" => "warning",
+            "❗" | "‼" => "important",
+            "ℹ" | "📝" => "note",
+            "🚨" | "🛑" => "caution",
+            _ => return None,
+        };
+        Some(class.to_string())
+    }
+
+    /// Returns the id of the block of `id`, or an empty string if ids are left to Pandoc.
+    fn anchor(&self, id: Uuid) -> String {
+        match self.block_ids {
+            BlockIds::None => "".to_string(),
+            BlockIds::Id | BlockIds::Slug => id.to_string(),
+        }
+    }
+
+    fn localize(&self, time: DateTime<Local>) -> DateTime<FixedOffset> {
+        match self.timezone {
+            Some(tz) => {
+                let time = time.with_timezone(&tz);
+                time.with_timezone(&time.offset().fix())
+            }
+            None => time.with_timezone(&time.offset().fix()),
+        }
+    }
+
+    /// Returns `time` in `--timezone` and `--date-format`.
+    fn format_date(&self, time: DateTime<Local>) -> String {
+        self.localize(time).format(&self.date_format).to_string()
+    }
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert every page in a Notion database into OUTPUT/<page id>.json
+    Database {
+        #[clap(short = 'i')]
+        id: String,
+        #[clap(short = 'o')]
+        output: PathBuf,
+        /// JSON passed as `filter` to the database query endpoint
+        #[clap(long)]
+        filter: Option<String>,
+        /// JSON passed as `sorts` to the database query endpoint
+        #[clap(long)]
+        sorts: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() {
     openssl_probe::init_ssl_cert_env_vars();
     let args = Args::parse();
-    let id = Uuid::parse_str(&args.id).expect("ID should be UUID");
-    let page = notion::Page::fetch(id, &args.secret).await;
-    let (title, date, lastmod) = notion::fetch_meta(id, &args.secret).await;
+    match args.command {
+        None => {
+            let id = args.id.unwrap_or_else(|| {
+                Args::command()
+                    .error(
+                        ErrorKind::MissingRequiredArgument,
+                        "-i <ID> is required unless a subcommand is given",
+                    )
+                    .exit()
+            });
+            let id = Uuid::parse_str(&id).expect("ID should be UUID");
+            let meta = notion::fetch_meta(id, &args.secret).await;
+            let (rsl, manifest) = convert(
+                meta,
+                &args.secret,
+                &args.options,
+                Path::new(""),
+                &mut notion::AncestorCache::new(),
+                &mut notion::UserCache::new(),
+            )
+            .await;
+            write_manifest(&args.options, manifest);
+            println!(
+                "{}",
+                serde_json::to_string(&rsl).expect("failed to serialize")
+            )
+        }
+        Some(Command::Database {
+            id,
+            output,
+            filter,
+            sorts,
+        }) => {
+            let id = Uuid::parse_str(&id).expect("ID should be UUID");
+            let filter = filter.map(|f| serde_json::from_str(&f).expect("filter should be JSON"));
+            let sorts = sorts.map(|s| serde_json::from_str(&s).expect("sorts should be JSON"));
+            std::fs::create_dir_all(&output).expect("failed to create output directory");
+            let mut manifest = assets::Manifest::new();
+            let mut cache = notion::AncestorCache::new();
+            let mut users = notion::UserCache::new();
+            for meta in notion::query_database(id, &args.secret, filter, sorts).await {
+                let path = output.join(format!("{}.json", meta.id));
+                let (rsl, assets) = convert(
+                    meta,
+                    &args.secret,
+                    &args.options,
+                    &output,
+                    &mut cache,
+                    &mut users,
+                )
+                .await;
+                manifest.extend(assets);
+                std::fs::write(
+                    &path,
+                    serde_json::to_string(&rsl).expect("failed to serialize"),
+                )
+                .unwrap_or_else(|_| panic!("failed to write {}", path.display()));
+            }
+            write_manifest(&args.options, manifest);
+        }
+    }
+}
+
+/// Converts the page of `meta`; `base` is the directory the result is written to.
+async fn convert(
+    mut meta: notion::PageMeta,
+    secret: &String,
+    options: &Options,
+    base: &Path,
+    cache: &mut notion::AncestorCache,
+    users: &mut notion::UserCache,
+) -> (pandoc::Pandoc, assets::Manifest) {
+    let mut page = notion::Page::fetch(meta.id, secret).await;
+    let page_comments = if options.comments != Comments::None {
+        page.attach_comments(
+            meta.id,
+            secret,
+            options.comments_concurrency as usize,
+            users,
+        )
+        .await
+    } else {
+        vec![]
+    };
+    if page.has_breadcrumb() {
+        let ancestors = notion::fetch_ancestors(&meta.parent, secret, cache).await;
+        page.attach_breadcrumbs(&ancestors);
+    }
+    if let Some(prefix) = &options.table_caption_prefix {
+        page.attach_table_captions(prefix);
+    }
+    preview::fetch(&mut page, &options.preview).await;
+    let manifest = assets::download(&mut page, &mut meta, &options.assets, base).await;
+    meta.created_by = notion::fetch_user_cached(meta.created_by, secret, users).await;
+    meta.last_edited_by = notion::fetch_user_cached(meta.last_edited_by, secret, users).await;
+    let toc = page.has_toc();
+    let mut blocks = page
+        .blocks
+        .into_iter()
+        .filter_map(|b| b.into_pandoc(options))
+        .collect::<Vec<_>>();
+    // comments on the page, which are shown above the page in Notion
+    blocks.splice(
+        0..0,
+        page_comments.into_iter().map(|comment| {
+            let (attr, content) = comment.into_pandoc(options);
+            pandoc::Block::Div(attr, vec![pandoc::Block::Para(content)])
+        }),
+    );
+    if options.block_ids != BlockIds::None {
+        assign_anchors(&mut blocks, options.block_ids);
+    }
+    if options.raw_inline {
+        pandoc::walk_inlines(&mut blocks, &mut |inline| {
+            if let pandoc::Inline::Code(_, text) = inline {
+                if let Some((text, format)) = split_raw_suffix(text) {
+                    *inline = pandoc::Inline::RawInline(pandoc::Format(format), text);
+                }
+            }
+        });
+    }
+    if options.comments == Comments::Note {
+        comments_to_notes(&mut blocks);
+    }
+    if options.citations {
+        citations::parse(&mut blocks);
+    }
+    // after the passes over inlines, which do not visit notes,
+    // and before the table of contents, which should not list the footnotes section
+    if let Some(title) = &options.footnotes {
+        footnotes::attach(&mut blocks, title);
+    }
+    if options.toc_block {
+        build_toc(&mut blocks);
+    }
     let rsl = pandoc::Pandoc {
         pandoc_api_version: pandoc::PANDOC_API_VERSION,
-        meta: pandoc::Meta(HashMap::from_iter([
+        meta: meta.into_pandoc(toc && !options.toc_block, options),
+        blocks,
+    };
+    (rsl, manifest)
+}
+
+/// Replaces ids of headings with slugs in `BlockIds::Slug` mode and rewrites
+/// links to blocks in the same page (`https://www.notion.so/Page-<page id>#<block id>`)
+/// into links to the anchors.
+fn assign_anchors(blocks: &mut [pandoc::Block], block_ids: BlockIds) {
+    let mut anchors = HashMap::new();
+    let mut slugs = HashMap::<String, usize>::new();
+    pandoc::walk_blocks(blocks, &mut |block| {
+        let slug = match block {
+            pandoc::Block::Header(_, _, inlines) if block_ids == BlockIds::Slug => {
+                Some(heading_slug(inlines, &mut slugs))
+            }
+            _ => None,
+        };
+        let attr = match block.attr_mut() {
+            Some(attr) => attr,
+            None => return,
+        };
+        let id = match Uuid::parse_str(&attr.0) {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        if block_ids == BlockIds::Slug {
+            attr.2.push(("data-notion-id".to_string(), id.to_string()));
+        }
+        if let Some(slug) = slug {
+            attr.0 = slug;
+        }
+        anchors.insert(id, attr.0.clone());
+    });
+    pandoc::walk_inlines(blocks, &mut |inline| {
+        if let pandoc::Inline::Link(_, _, pandoc::Target(url, _)) = inline {
+            let anchor = url
+                .rsplit_once('#')
+                .and_then(|(_, fragment)| Uuid::parse_str(fragment).ok())
+                .and_then(|id| anchors.get(&id));
+            if let Some(anchor) = anchor {
+                *url = format!("#{}", anchor);
+            }
+        }
+    });
+}
+
+/// Returns the slug of a heading, deduplicated with `slugs` like the auto
+/// identifiers of Pandoc.
+fn heading_slug(inlines: &[pandoc::Inline], slugs: &mut HashMap<String, usize>) -> String {
+    let slug = match slugify(&pandoc::stringify(inlines)) {
+        slug if slug.is_empty() => "section".to_string(),
+        slug => slug,
+    };
+    let count = slugs.entry(slug.clone()).or_default();
+    *count += 1;
+    match *count {
+        1 => slug,
+        n => format!("{}-{}", slug, n - 1),
+    }
+}
+
+/// Fills the Divs of table_of_contents blocks with nested lists of links to
+/// the headings, giving slugs to headings without ids.
+fn build_toc(blocks: &mut [pandoc::Block]) {
+    let mut headings = vec![];
+    let mut slugs = HashMap::new();
+    pandoc::walk_blocks(blocks, &mut |block| {
+        if let pandoc::Block::Header(level, pandoc::Attr(id, ..), inlines) = block {
+            if id.is_empty() {
+                *id = heading_slug(inlines, &mut slugs);
+            }
+            headings.push((*level, id.clone(), pandoc::stringify(inlines)));
+        }
+    });
+    pandoc::walk_blocks(blocks, &mut |block| {
+        if let pandoc::Block::Div(pandoc::Attr(_, classes, _), content) = block {
+            if classes.iter().any(|c| c == "table_of_contents") && content.is_empty() {
+                *content = vec![pandoc::Block::BulletList(toc_items(&headings))];
+            }
+        }
+    });
+}
+
+/// Returns the list items of `headings`, each followed by the deeper headings
+/// up to the next heading of the same or a higher level.
+fn toc_items(headings: &[(u64, String, String)]) -> Vec<Vec<pandoc::Block>> {
+    let mut items = vec![];
+    let mut rest = headings;
+    while let Some(((level, id, text), tail)) = rest.split_first() {
+        let end = tail
+            .iter()
+            .position(|h| h.0 <= *level)
+            .unwrap_or(tail.len());
+        let mut item = vec![pandoc::Block::Plain(vec![pandoc::Inline::Str(
+            text.clone(),
+        )
+        .into_link(format!("#{}", id))])];
+        if end > 0 {
+            item.push(pandoc::Block::BulletList(toc_items(&tail[..end])));
+        }
+        items.push(item);
+        rest = &tail[end..];
+    }
+    items
+}
+
+fn write_manifest(options: &Options, manifest: assets::Manifest) {
+    if let Some(path) = &options.assets.assets_manifest {
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(&manifest).expect("failed to serialize"),
+        )
+        .unwrap_or_else(|_| panic!("failed to write {}", path.display()));
+    }
+}
+
+impl notion::PageMeta {
+    fn into_pandoc(self, toc: bool, options: &Options) -> pandoc::Meta {
+        let title = self.title();
+        let title_plain = join(title.iter().map(|r| r.plain_text()), "");
+        let mut meta = HashMap::from_iter([
             (
                 "date".to_string(),
-                pandoc::MetaValue::MetaString(date.date_naive().to_string()),
+                pandoc::MetaValue::MetaString(options.format_date(self.created_time)),
             ),
             (
                 "lastmod".to_string(),
-                pandoc::MetaValue::MetaString(lastmod.date_naive().to_string()),
+                pandoc::MetaValue::MetaString(options.format_date(self.last_edited_time)),
+            ),
+            (
+                "created-time".to_string(),
+                pandoc::MetaValue::MetaString(options.localize(self.created_time).to_rfc3339()),
+            ),
+            (
+                "last-edited-time".to_string(),
+                pandoc::MetaValue::MetaString(options.localize(self.last_edited_time).to_rfc3339()),
             ),
-            ("title".to_string(), pandoc::MetaValue::MetaString(title)),
+            ("created-by".to_string(), self.created_by.into_pandoc()),
             (
-                "toc".to_string(),
-                pandoc::MetaValue::MetaBool(page.has_toc()),
+                "last-edited-by".to_string(),
+                self.last_edited_by.into_pandoc(),
             ),
-        ])),
-        blocks: page
-            .blocks
-            .into_iter()
-            .filter_map(|b| b.to_pandoc())
-            .collect(),
+            (
+                "title".to_string(),
+                pandoc::MetaValue::MetaInlines(notion::Inline { rich_text: title }.into_pandoc()),
+            ),
+            (
+                "title-plain".to_string(),
+                pandoc::MetaValue::MetaString(title_plain),
+            ),
+            ("toc".to_string(), pandoc::MetaValue::MetaBool(toc)),
+        ]);
+        if let Some(icon) = self.icon {
+            meta.insert("icon".to_string(), icon.into_pandoc());
+        }
+        if let Some(cover) = self.cover {
+            let (_, url, loc) = notion::Block::unpack_file(cover);
+            meta.insert("cover".to_string(), file_meta(url, loc));
+        }
+        meta.extend(
+            self.properties
+                .into_iter()
+                .filter_map(|(k, v)| v.into_pandoc(options).map(|v| (slugify(&k), v))),
+        );
+        pandoc::Meta(meta)
+    }
+}
+
+/// Returns the class of the diagram filters for a code block of `class`,
+/// or `None` if it is not a diagram.
+///
+/// Notion knows only Mermaid, so Graphviz and PlantUML in plain text are
+/// recognized by their first keyword.
+fn diagram_engine(class: Option<&str>, text: &str) -> Option<&'static str> {
+    let text = text.trim_start();
+    match class {
+        Some("mermaid") => Some("mermaid"),
+        Some("plantuml") => Some("plantuml"),
+        Some("dot" | "graphviz") => Some("dot"),
+        None if text.starts_with("@startuml") => Some("plantuml"),
+        None if ["digraph", "graph", "strict"].iter().any(|k| {
+            text.strip_prefix(k)
+                .and_then(|rest| rest.chars().next())
+                .is_some_and(|c| c.is_whitespace() || c == '{')
+        }) =>
+        {
+            Some("dot")
+        }
+        _ => None,
+    }
+}
+
+/// Whether a caption looks like a file name such as `main.rs` or `src/lib.rs`.
+fn is_file_name(s: &str) -> bool {
+    !s.is_empty() && !s.contains(char::is_whitespace) && s.contains(['.', '/'])
+}
+
+/// Appends `comments` as Spans to the text of `block`, or puts them in a
+/// paragraph after `block` if it has no text, e.g. a figure or a code block.
+fn add_comments(block: &mut pandoc::Block, comments: Vec<notion::Comment>, options: &Options) {
+    if comments.is_empty() {
+        return;
+    }
+    let spans = comments.into_iter().map(|comment| {
+        let (attr, content) = comment.into_pandoc(options);
+        pandoc::Inline::Span(attr, content)
+    });
+    let inlines = match block {
+        pandoc::Block::Plain(inlines)
+        | pandoc::Block::Para(inlines)
+        | pandoc::Block::Header(_, _, inlines) => Some(inlines),
+        // quotes and callouts
+        pandoc::Block::BlockQuote(blocks) | pandoc::Block::Div(_, blocks) => {
+            match blocks.first_mut() {
+                Some(pandoc::Block::Plain(inlines) | pandoc::Block::Para(inlines)) => Some(inlines),
+                _ => None,
+            }
+        }
+        _ => None,
     };
-    println!(
-        "{}",
-        serde_json::to_string(&rsl).expect("failed to serialize")
+    if let Some(inlines) = inlines {
+        inlines.extend(spans);
+        return;
+    }
+    let commented = std::mem::replace(block, pandoc::Block::HorizontalRule);
+    *block = pandoc::Block::Div(
+        pandoc::Attr::default(),
+        vec![commented, pandoc::Block::Para(spans.collect())],
+    );
+}
+
+/// Replaces Spans of comments with notes prefixed by their authors.
+fn comments_to_notes(blocks: &mut [pandoc::Block]) {
+    pandoc::walk_inlines(blocks, &mut |inline| {
+        let (attrs, content) = match inline {
+            pandoc::Inline::Span(pandoc::Attr(_, classes, attrs), content)
+                if classes.iter().any(|c| c == "comment") =>
+            {
+                (attrs, content)
+            }
+            _ => return,
+        };
+        let author = attrs
+            .iter()
+            .find(|(k, _)| k == "author")
+            .map(|(_, v)| v.clone())
+            .unwrap_or_default();
+        let mut inlines = vec![
+            pandoc::Inline::Strong(vec![pandoc::Inline::Str(format!("{}:", author))]),
+            pandoc::Inline::Space,
+        ];
+        inlines.append(content);
+        *inline = pandoc::Inline::Note(vec![pandoc::Block::Para(inlines)]);
+    });
+}
+
+/// Returns the Pandoc format of raw code blocks in `language` of Notion.
+fn raw_format(language: &str) -> Option<&'static str> {
+    match language {
+        "html" => Some("html"),
+        "latex" => Some("latex"),
+        _ => None,
+    }
+}
+
+/// Splits `text{=format}` into the text and the format, like the raw
+/// attribute of Pandoc's Markdown.
+fn split_raw_suffix(s: &str) -> Option<(String, String)> {
+    let (text, format) = s.strip_suffix('}')?.rsplit_once("{=")?;
+    if text.is_empty()
+        || format.is_empty()
+        || !format
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+    {
+        return None;
+    }
+    Some((text.to_string(), format.to_string()))
+}
+
+/// Escapes `s` for text and attribute values of raw HTML.
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Lowercases `s` and replaces every run of non-alphanumeric characters with `-`.
+fn slugify(s: &str) -> String {
+    join(
+        s.to_lowercase()
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty()),
+        "-",
     )
 }
 
+impl notion::Property {
+    fn into_pandoc(self, options: &Options) -> Option<pandoc::MetaValue> {
+        match self {
+            // the title property is exported as `title`
+            notion::Property::Title { .. } => None,
+            notion::Property::RichText { rich_text } => Some(pandoc::MetaValue::MetaInlines(
+                notion::Inline { rich_text }.into_pandoc(),
+            )),
+            notion::Property::Number { number } => {
+                number.map(|n| pandoc::MetaValue::MetaString(n.to_string()))
+            }
+            notion::Property::Select { select: option }
+            | notion::Property::Status { status: option } => {
+                option.map(|o| pandoc::MetaValue::MetaString(o.name))
+            }
+            notion::Property::MultiSelect { multi_select } => Some(pandoc::MetaValue::MetaList(
+                multi_select
+                    .into_iter()
+                    .map(|o| pandoc::MetaValue::MetaString(o.name))
+                    .collect(),
+            )),
+            notion::Property::Date { date } => date.map(|d| d.into_pandoc()),
+            notion::Property::People { people } => Some(pandoc::MetaValue::MetaList(
+                people.into_iter().map(|u| u.into_pandoc()).collect(),
+            )),
+            notion::Property::Files { files } => Some(pandoc::MetaValue::MetaList(
+                files
+                    .into_iter()
+                    .map(|f| {
+                        let (_, url, _) = notion::Block::unpack_file(f);
+                        pandoc::MetaValue::MetaString(url)
+                    })
+                    .collect(),
+            )),
+            notion::Property::Checkbox { checkbox } => Some(pandoc::MetaValue::MetaBool(checkbox)),
+            notion::Property::Url { url: value }
+            | notion::Property::Email { email: value }
+            | notion::Property::PhoneNumber {
+                phone_number: value,
+            } => value.map(pandoc::MetaValue::MetaString),
+            notion::Property::Formula { formula } => match formula {
+                notion::Formula::String { string } => string.map(pandoc::MetaValue::MetaString),
+                notion::Formula::Number { number } => {
+                    number.map(|n| pandoc::MetaValue::MetaString(n.to_string()))
+                }
+                notion::Formula::Boolean { boolean } => Some(pandoc::MetaValue::MetaBool(boolean)),
+                notion::Formula::Date { date } => date.map(|d| d.into_pandoc()),
+            },
+            notion::Property::Relation { relation } => Some(pandoc::MetaValue::MetaList(
+                relation
+                    .into_iter()
+                    .map(|p| pandoc::MetaValue::MetaString(p.id.to_string()))
+                    .collect(),
+            )),
+            notion::Property::Rollup { rollup } => match rollup {
+                notion::Rollup::Number { number } => {
+                    number.map(|n| pandoc::MetaValue::MetaString(n.to_string()))
+                }
+                notion::Rollup::Date { date } => date.map(|d| d.into_pandoc()),
+                notion::Rollup::Array { array } => Some(pandoc::MetaValue::MetaList(
+                    array
+                        .into_iter()
+                        .filter_map(|p| p.into_pandoc(options))
+                        .collect(),
+                )),
+                notion::Rollup::Unsupported => None,
+            },
+            notion::Property::CreatedTime { created_time: time }
+            | notion::Property::LastEditedTime {
+                last_edited_time: time,
+            } => Some(pandoc::MetaValue::MetaString(options.format_date(time))),
+            notion::Property::CreatedBy { created_by: user }
+            | notion::Property::LastEditedBy {
+                last_edited_by: user,
+            } => Some(user.into_pandoc()),
+            notion::Property::UniqueId { unique_id } => unique_id.number.map(|n| {
+                pandoc::MetaValue::MetaString(match unique_id.prefix {
+                    Some(prefix) => format!("{}-{}", prefix, n),
+                    None => n.to_string(),
+                })
+            }),
+            notion::Property::Unsupported => None,
+        }
+    }
+}
+
+impl notion::Date {
+    fn into_pandoc(self) -> pandoc::MetaValue {
+        match self.end {
+            Some(end) => pandoc::MetaValue::MetaMap(HashMap::from_iter([
+                (
+                    "start".to_string(),
+                    pandoc::MetaValue::MetaString(self.start),
+                ),
+                ("end".to_string(), pandoc::MetaValue::MetaString(end)),
+            ])),
+            None => pandoc::MetaValue::MetaString(self.start),
+        }
+    }
+}
+
+impl notion::Icon {
+    fn into_pandoc(self) -> pandoc::MetaValue {
+        match self {
+            notion::Icon::Emoji { emoji } => pandoc::MetaValue::MetaMap(HashMap::from_iter([(
+                "emoji".to_string(),
+                pandoc::MetaValue::MetaString(emoji),
+            )])),
+            notion::Icon::External { external } => file_meta(external.url, "external".to_string()),
+            notion::Icon::File { file } => file_meta(file.url, "internal".to_string()),
+        }
+    }
+}
+
+fn file_meta(url: String, loc: String) -> pandoc::MetaValue {
+    pandoc::MetaValue::MetaMap(HashMap::from_iter([
+        ("url".to_string(), pandoc::MetaValue::MetaString(url)),
+        ("location".to_string(), pandoc::MetaValue::MetaString(loc)),
+    ]))
+}
+
+impl notion::Comment {
+    /// Returns the attributes with class `comment`, the author and the time, and the text.
+    fn into_pandoc(self, options: &Options) -> (pandoc::Attr, Vec<pandoc::Inline>) {
+        let author = self
+            .created_by
+            .name
+            .unwrap_or_else(|| self.created_by.id.to_string());
+        (
+            pandoc::Attr(
+                "".to_string(),
+                vec!["comment".to_string()],
+                vec![
+                    ("author".to_string(), author),
+                    (
+                        "time".to_string(),
+                        options.localize(self.created_time).to_rfc3339(),
+                    ),
+                ],
+            ),
+            notion::Inline {
+                rich_text: self.rich_text,
+            }
+            .into_pandoc(),
+        )
+    }
+}
+
+impl notion::User {
+    fn into_pandoc(self) -> pandoc::MetaValue {
+        pandoc::MetaValue::MetaString(self.name.unwrap_or_else(|| self.id.to_string()))
+    }
+}
+
 impl notion::Block {
-    fn to_pandoc(self) -> Option<pandoc::Block> {
+    fn into_pandoc(mut self, options: &Options) -> Option<pandoc::Block> {
+        let comments = std::mem::take(&mut self.comments);
+        let mut block = self.convert(options)?;
+        add_comments(&mut block, comments, options);
+        Some(block)
+    }
+
+    fn convert(self, options: &Options) -> Option<pandoc::Block> {
         match self.var {
-            notion::Var::Paragraph { inline } => Some(pandoc::Block::Para(inline.to_pandoc())),
+            notion::Var::Paragraph { inline } => Some(pandoc::Block::Para(inline.into_pandoc())),
             notion::Var::Heading1 { inline } => Some(pandoc::Block::Header(
                 2,
-                pandoc::Attr::default(),
-                inline.to_pandoc(),
+                pandoc::Attr(options.anchor(self.id), vec![], vec![]),
+                inline.into_pandoc(),
             )),
             notion::Var::Heading2 { inline } => Some(pandoc::Block::Header(
                 3,
-                pandoc::Attr::default(),
-                inline.to_pandoc(),
+                pandoc::Attr(options.anchor(self.id), vec![], vec![]),
+                inline.into_pandoc(),
             )),
             notion::Var::Heading3 { inline } => Some(pandoc::Block::Header(
                 4,
-                pandoc::Attr::default(),
-                inline.to_pandoc(),
+                pandoc::Attr(options.anchor(self.id), vec![], vec![]),
+                inline.into_pandoc(),
             )),
             notion::Var::Quote { inline } => Some(pandoc::Block::BlockQuote(
-                inline.to_pandoc_with_children(self.children),
+                inline.into_pandoc_with_children(self.children, options),
             )),
 
-            notion::Var::Callout { callout } => Some(pandoc::Block::Div(
-                pandoc::Attr("".to_string(), vec!["callout".to_string()], vec![]),
-                vec![pandoc::Block::Plain(
-                    callout
-                        .rich_text
-                        .into_iter()
-                        .map(|r| r.to_pandoc())
-                        .collect(),
-                )],
-            )),
+            notion::Var::Callout { callout } => {
+                let admonition = options.admonition_class(&callout.icon);
+                let classes = match (options.admonition, admonition) {
+                    (Admonition::Class, Some(class)) => vec!["callout".to_string(), class],
+                    (Admonition::Quarto, Some(class)) => vec![format!("callout-{}", class)],
+                    _ => vec!["callout".to_string()],
+                };
+                let icon = match callout.icon {
+                    notion::Icon::Emoji { emoji } => emoji,
+                    notion::Icon::External { external } => external.url,
+                    notion::Icon::File { file } => file.url,
+                };
+                Some(pandoc::Block::Div(
+                    pandoc::Attr(
+                        options.anchor(self.id),
+                        classes,
+                        vec![("icon".to_string(), icon)],
+                    ),
+                    notion::Inline {
+                        rich_text: callout.rich_text,
+                    }
+                    .into_pandoc_with_children(self.children, options),
+                ))
+            }
 
             // {Bulleted, Numbered, ToDo, Toggle}ListItem should be
             // in a children of BulletedList/NumberedList node
@@ -94,22 +890,7 @@ impl notion::Block {
             | notion::Var::ToDoListItem { .. }
             | notion::Var::ToggleListItem { .. } => panic!("list item in top-level"),
 
-            notion::Var::Code { code } => {
-                let text = join(
-                    code.rich_text.into_iter().map(|r| match r {
-                        notion::RichText::Text {
-                            annotations: _,
-                            text,
-                        } => text.content,
-                        _ => panic!("mention or equation in title"),
-                    }),
-                    "",
-                );
-                Some(pandoc::Block::CodeBlock(
-                    pandoc::Attr("".to_string(), vec![code.language], vec![]),
-                    text,
-                ))
-            }
+            notion::Var::Code { code } => Some(Self::convert_code(self.id, code, options)),
             notion::Var::Equation { equation } => {
                 Some(pandoc::Block::Para(vec![pandoc::Inline::Math(
                     pandoc::MathType::DisplayMath,
@@ -119,40 +900,95 @@ impl notion::Block {
 
             notion::Var::Image { file } => {
                 let (caption, url, loc) = Self::unpack_file(file);
-                Some(pandoc::Block::Para(vec![pandoc::Inline::Image(
+                let image = pandoc::Inline::Image(
                     pandoc::Attr("".to_string(), vec![loc], vec![]),
-                    caption.into_iter().map(|r| r.to_pandoc()).collect(),
+                    caption.iter().cloned().map(|r| r.into_pandoc()).collect(),
                     pandoc::Target(url, "".to_string()),
-                )]))
-            }
-            notion::Var::Video { file } => {
-                let (caption, url, loc) = Self::unpack_file(file);
-                Some(Self::link(url, caption, vec!["video".to_string(), loc]))
-            }
-            notion::Var::File { file } => {
-                let (caption, url, loc) = Self::unpack_file(file);
-                Some(Self::link(url, caption, vec!["file".to_string(), loc]))
+                );
+                if caption.is_empty() {
+                    Some(pandoc::Block::Para(vec![image]))
+                } else {
+                    Some(pandoc::Block::Figure(
+                        pandoc::Attr(self.id.to_string(), vec![], vec![]),
+                        pandoc::Caption(
+                            None,
+                            vec![pandoc::Block::Plain(
+                                caption.into_iter().map(|r| r.into_pandoc()).collect(),
+                            )],
+                        ),
+                        vec![pandoc::Block::Plain(vec![image])],
+                    ))
+                }
             }
-            notion::Var::PDF { file } => {
-                let (caption, url, loc) = Self::unpack_file(file);
-                Some(Self::link(url, caption, vec!["pdf".to_string(), loc]))
+            notion::Var::Video {
+                file:
+                    notion::File::External {
+                        caption, external, ..
+                    },
+            } if embed::resolve(&external.url).is_some() => Some(Self::convert_embed(
+                external.url,
+                caption,
+                vec!["video".to_string(), "external".to_string()],
+                options,
+            )),
+            notion::Var::Video { file } => Some(Self::file_link(file, "video")),
+            notion::Var::Audio { file } if options.audio_html => {
+                let (caption, url, _) = Self::unpack_file(file);
+                let title = join(caption.iter().map(|r| r.plain_text()), "");
+                Some(pandoc::Block::RawBlock(
+                    pandoc::Format("html".to_string()),
+                    if title.is_empty() {
+                        format!(r#"<audio controls src="{}"></audio>"#, escape_html(&url))
+                    } else {
+                        format!(
+                            r#"<audio controls src="{}" title="{}"></audio>"#,
+                            escape_html(&url),
+                            escape_html(&title)
+                        )
+                    },
+                ))
             }
+            notion::Var::Audio { file } => Some(Self::file_link(file, "audio")),
+            notion::Var::File { file } => Some(Self::file_link(file, "file")),
+            notion::Var::PDF { file } => Some(Self::file_link(file, "pdf")),
 
-            notion::Var::Embed { embed } | notion::Var::Bookmark { embed } => Some(Self::link(
+            notion::Var::Embed { embed } => Some(Self::convert_embed(
                 embed.url,
                 embed.caption,
                 vec!["embed".to_string()],
+                options,
             )),
+            notion::Var::Bookmark { embed } => {
+                let attrs = embed
+                    .preview
+                    .map(|p| {
+                        [
+                            ("title", p.title),
+                            ("description", p.description),
+                            ("favicon", p.favicon),
+                        ]
+                        .into_iter()
+                        .filter_map(|(k, v)| v.map(|v| (k.to_string(), v)))
+                        .collect()
+                    })
+                    .unwrap_or_default();
+                Some(Self::link(
+                    embed.url,
+                    embed.caption,
+                    vec!["bookmark".to_string()],
+                    attrs,
+                ))
+            }
             notion::Var::LinkPreview { link_preview } => {
                 Some(pandoc::Block::Para(vec![pandoc::Inline::Str(
                     link_preview.url.clone(),
                 )
-                .to_link(link_preview.url)]))
+                .into_link(link_preview.url)]))
             }
             notion::Var::LinkToPage { link_to_page } => match link_to_page {
                 notion::LinkToPage::PageId { page_id } => Some(pandoc::Block::Div(
                     pandoc::Attr(
-                        "".to_string(),
+                        options.anchor(self.id),
                         vec!["link_to_page".to_string()],
                         vec![("id".to_string(), page_id.to_string())],
                     ),
@@ -173,14 +1009,24 @@ impl notion::Block {
                     let col_specs = (0..table.table_width)
                         .map(|_| pandoc::ColSpec::default())
                         .collect();
+                    let caption = if table.caption.is_empty() {
+                        vec![]
+                    } else {
+                        vec![pandoc::Block::Plain(
+                            notion::Inline {
+                                rich_text: table.caption,
+                            }
+                            .into_pandoc(),
+                        )]
+                    };
                     Some(pandoc::Block::Table(
-                        pandoc::Attr::default(),
-                        pandoc::Caption::default(),
+                        pandoc::Attr(self.id.to_string(), vec![], vec![]),
+                        pandoc::Caption(None, caption),
                         col_specs,
                         pandoc::TableHead(pandoc::Attr::default(), header),
                         vec![pandoc::TableBody(
                             pandoc::Attr::default(),
-                            pandoc::RowHeadColumns(0),
+                            pandoc::RowHeadColumns(if table.has_row_header { 1 } else { 0 }),
                             vec![],
                             body,
                         )],
@@ -193,13 +1039,51 @@ impl notion::Block {
             notion::Var::TableRow { .. } => panic!("table row in top level"),
 
             notion::Var::Divider => Some(pandoc::Block::HorizontalRule),
+            // filled by `build_toc` after the headings get their ids
+            notion::Var::TableOfContents if options.toc_block => Some(pandoc::Block::Div(
+                pandoc::Attr(
+                    options.anchor(self.id),
+                    vec!["table_of_contents".to_string()],
+                    vec![],
+                ),
+                vec![],
+            )),
             notion::Var::TableOfContents => None,
+            notion::Var::Breadcrumb { ancestors } if ancestors.is_empty() => None,
+            notion::Var::Breadcrumb { ancestors } => {
+                let links = ancestors.into_iter().map(|a| {
+                    vec![pandoc::Inline::Link(
+                        pandoc::Attr::default(),
+                        notion::Inline { rich_text: a.title }.into_pandoc(),
+                        pandoc::Target(a.url, "".to_string()),
+                    )]
+                });
+                let separator = || {
+                    vec![
+                        pandoc::Inline::Space,
+                        pandoc::Inline::Str("/".to_string()),
+                        pandoc::Inline::Space,
+                    ]
+                };
+                Some(pandoc::Block::Div(
+                    pandoc::Attr(
+                        options.anchor(self.id),
+                        vec!["breadcrumb".to_string()],
+                        vec![],
+                    ),
+                    vec![pandoc::Block::Plain(
+                        Itertools::intersperse_with(links, separator)
+                            .flatten()
+                            .collect(),
+                    )],
+                ))
+            }
 
             notion::Var::BulletedList => Some(pandoc::Block::BulletList(
                 self.children
                     .expect("bulleted list should have children")
                     .into_iter()
-                    .map(Self::convert_list_item)
+                    .map(|x| Self::convert_list_item(x, options))
                     .collect(),
             )),
             notion::Var::NumberedList => Some(pandoc::Block::OrderedList(
@@ -211,7 +1095,7 @@ impl notion::Block {
                 self.children
                     .expect("numbered list should have children")
                     .into_iter()
-                    .map(Self::convert_list_item)
+                    .map(|x| Self::convert_list_item(x, options))
                     .collect(),
             )),
 
@@ -219,23 +1103,191 @@ impl notion::Block {
         }
     }
 
+    fn convert_code(id: Uuid, code: notion::Code, options: &Options) -> pandoc::Block {
+        let text = join(code.rich_text.iter().map(|r| r.plain_text()), "");
+        let caption = join(code.caption.iter().map(|r| r.plain_text()), "");
+        if options.raw_caption.as_ref() == Some(&caption) {
+            if let Some(format) = raw_format(&code.language) {
+                return pandoc::Block::RawBlock(pandoc::Format(format.to_string()), text);
+            }
+        }
+        let class = options.language_class(&code.language);
+        if options.diagram != Diagram::None {
+            if let Some(engine) = diagram_engine(class.as_deref(), &text) {
+                return Self::convert_diagram(id, engine, code.caption, text, options);
+            }
+        }
+        let mut attr = pandoc::Attr("".to_string(), class.into_iter().collect(), vec![]);
+        if code.caption.is_empty() {
+            attr.0 = options.anchor(id);
+            return pandoc::Block::CodeBlock(attr, text);
+        }
+        match options.code_caption {
+            CodeCaption::Attribute => {
+                let key = if is_file_name(&caption) {
+                    "filename"
+                } else {
+                    "caption"
+                };
+                attr.0 = options.anchor(id);
+                attr.2.push((key.to_string(), caption));
+                pandoc::Block::CodeBlock(attr, text)
+            }
+            CodeCaption::Figure => pandoc::Block::Figure(
+                pandoc::Attr(id.to_string(), vec![], vec![]),
+                pandoc::Caption(
+                    None,
+                    vec![pandoc::Block::Plain(
+                        notion::Inline {
+                            rich_text: code.caption,
+                        }
+                        .into_pandoc(),
+                    )],
+                ),
+                vec![pandoc::Block::CodeBlock(attr, text)],
+            ),
+            CodeCaption::Div => pandoc::Block::Div(
+                pandoc::Attr(options.anchor(id), vec!["code".to_string()], vec![]),
+                vec![
+                    pandoc::Block::Div(
+                        pandoc::Attr("".to_string(), vec!["caption".to_string()], vec![]),
+                        vec![pandoc::Block::Plain(
+                            notion::Inline {
+                                rich_text: code.caption,
+                            }
+                            .into_pandoc(),
+                        )],
+                    ),
+                    pandoc::Block::CodeBlock(attr, text),
+                ],
+            ),
+        }
+    }
+
+    fn convert_diagram(
+        id: Uuid,
+        engine: &str,
+        caption: Vec<notion::RichText>,
+        text: String,
+        options: &Options,
+    ) -> pandoc::Block {
+        let classes = vec![engine.to_string(), "diagram".to_string()];
+        match options.diagram {
+            Diagram::Figure => pandoc::Block::Figure(
+                pandoc::Attr(id.to_string(), vec![], vec![]),
+                pandoc::Caption(
+                    None,
+                    if caption.is_empty() {
+                        vec![]
+                    } else {
+                        vec![pandoc::Block::Plain(
+                            notion::Inline { rich_text: caption }.into_pandoc(),
+                        )]
+                    },
+                ),
+                vec![pandoc::Block::CodeBlock(
+                    pandoc::Attr("".to_string(), classes, vec![]),
+                    text,
+                )],
+            ),
+            _ if caption.is_empty() => {
+                pandoc::Block::CodeBlock(pandoc::Attr(options.anchor(id), classes, vec![]), text)
+            }
+            _ => pandoc::Block::CodeBlock(
+                pandoc::Attr(
+                    options.anchor(id),
+                    classes,
+                    vec![(
+                        "caption".to_string(),
+                        join(caption.iter().map(|r| r.plain_text()), ""),
+                    )],
+                ),
+                text,
+            ),
+        }
+    }
+
     fn unpack_file(file: notion::File) -> (Vec<notion::RichText>, String, String) {
         match file {
-            notion::File::File { caption, file } => (caption, file.url, "internal".to_string()),
-            notion::File::External { caption, external } => {
-                (caption, external.url, "external".to_string())
+            notion::File::File { caption, file, .. } => (caption, file.url, "internal".to_string()),
+            notion::File::External {
+                caption, external, ..
+            } => (caption, external.url, "external".to_string()),
+        }
+    }
+
+    /// Returns a link to `file` titled by its caption, or by its file name if
+    /// uncaptioned, with its content type and size (if downloaded) as attributes.
+    fn file_link(file: notion::File, class: &str) -> pandoc::Block {
+        let (name, info) = match &file {
+            notion::File::File { name, info, .. } | notion::File::External { name, info, .. } => {
+                (name.clone(), info.clone())
             }
+        };
+        let (caption, url, loc) = Self::unpack_file(file);
+        let content_type = info
+            .as_ref()
+            .and_then(|i| i.content_type.as_deref())
+            .map(|c| c.split(';').next().unwrap_or("").trim())
+            .filter(|c| !c.is_empty() && *c != "application/octet-stream")
+            .or_else(|| assets::content_type(name.as_deref().unwrap_or(&url)))
+            .map(String::from);
+        let mut attrs = vec![];
+        attrs.extend(content_type.map(|c| ("type".to_string(), c)));
+        attrs.extend(info.map(|i| ("size".to_string(), i.size.to_string())));
+        let text = if !caption.is_empty() {
+            caption.into_iter().map(|r| r.into_pandoc()).collect()
+        } else {
+            vec![pandoc::Inline::Str(name.unwrap_or_else(|| url.clone()))]
+        };
+        pandoc::Block::Para(vec![pandoc::Inline::Link(
+            pandoc::Attr("".to_string(), vec![class.to_string(), loc], attrs),
+            text,
+            pandoc::Target(url, "".to_string()),
+        )])
+    }
+
+    /// Returns a link to `url` with the attributes of its provider if known, preceded
+    /// by the HTML of the provider in a Div with `--embed-html`.
+    fn convert_embed(
+        url: String,
+        caption: Vec<notion::RichText>,
+        classes: Vec<String>,
+        options: &Options,
+    ) -> pandoc::Block {
+        let embed = match embed::resolve(&url) {
+            Some(embed) => embed,
+            None => return Self::link(url, caption, classes, vec![]),
+        };
+        let mut attrs = vec![("provider".to_string(), embed.provider.to_string())];
+        attrs.extend(embed.attrs);
+        let link = Self::link(url, caption, classes.clone(), attrs.clone());
+        if !options.embed_html {
+            return link;
         }
+        // the raw HTML is dropped in other formats, leaving the link
+        pandoc::Block::Div(
+            pandoc::Attr("".to_string(), classes, attrs),
+            vec![
+                pandoc::Block::RawBlock(pandoc::Format("html".to_string()), embed.html),
+                link,
+            ],
+        )
     }
 
-    fn link(url: String, cap: Vec<notion::RichText>, attr: Vec<String>) -> pandoc::Block {
+    fn link(
+        url: String,
+        cap: Vec<notion::RichText>,
+        classes: Vec<String>,
+        attrs: Vec<(String, String)>,
+    ) -> pandoc::Block {
         let caption = if cap.is_empty() {
             vec![pandoc::Inline::Str(url.clone())]
         } else {
-            cap.into_iter().map(|r| r.to_pandoc()).collect()
+            cap.into_iter().map(|r| r.into_pandoc()).collect()
         };
         pandoc::Block::Para(vec![pandoc::Inline::Link(
-            pandoc::Attr("".to_string(), attr, vec![]),
+            pandoc::Attr("".to_string(), classes, attrs),
             caption,
             pandoc::Target(url, "".to_string()),
         )])
@@ -262,36 +1314,40 @@ impl notion::Block {
             pandoc::RowSpan(1),
             pandoc::ColSpan(1),
             vec![pandoc::Block::Plain(
-                x.into_iter().map(|r| r.to_pandoc()).collect(),
+                x.into_iter().map(|r| r.into_pandoc()).collect(),
             )],
         )
     }
 
-    fn convert_list_item(x: notion::Block) -> Vec<pandoc::Block> {
-        match x.var {
+    fn convert_list_item(x: notion::Block, options: &Options) -> Vec<pandoc::Block> {
+        let mut result = match x.var {
             notion::Var::BulletedListItem { inline }
             | notion::Var::NumberedListItem { inline }
-            | notion::Var::ToggleListItem { inline } => inline.to_pandoc_with_children(x.children),
+            | notion::Var::ToggleListItem { inline } => {
+                inline.into_pandoc_with_children(x.children, options)
+            }
             notion::Var::ToDoListItem { to_do } => {
-                let check_mark = format!("{}", if to_do.checked { "☒" } else { "☐" });
+                let check_mark = if to_do.checked { "☒" } else { "☐" }.to_string();
                 let mut text_with_box =
                     vec![pandoc::Inline::Str(check_mark), pandoc::Inline::Space];
-                text_with_box.extend(to_do.rich_text.into_iter().map(|r| r.to_pandoc()));
+                text_with_box.extend(to_do.rich_text.into_iter().map(|r| r.into_pandoc()));
                 let mut result = vec![pandoc::Block::Plain(text_with_box)];
                 if let Some(children) = x.children {
-                    result.extend(children.into_iter().filter_map(|b| b.to_pandoc()));
+                    result.extend(children.into_iter().filter_map(|b| b.into_pandoc(options)));
                 }
                 result
             }
             _ => panic!("child of list should be a list item"),
-        }
+        };
+        add_comments(&mut result[0], x.comments, options);
+        result
     }
 }
 
 impl notion::Inline {
-    fn to_pandoc(self) -> Vec<pandoc::Inline> {
+    fn into_pandoc(self) -> Vec<pandoc::Inline> {
         let mut result = vec![];
-        for inline in self.rich_text.into_iter().map(|r| r.to_pandoc()) {
+        for inline in self.rich_text.into_iter().map(|r| r.into_pandoc()) {
             if let Some(pandoc::Inline::Link(attr_last, vec_last, trg_last)) = result.last_mut() {
                 if let pandoc::Inline::Link(attr, mut vec, trg) = inline {
                     if *attr_last == attr && *trg_last == trg {
@@ -307,17 +1363,21 @@ impl notion::Inline {
         result
     }
 
-    fn to_pandoc_with_children(self, children: Option<Vec<notion::Block>>) -> Vec<pandoc::Block> {
-        let mut result = vec![pandoc::Block::Plain(self.to_pandoc())];
+    fn into_pandoc_with_children(
+        self,
+        children: Option<Vec<notion::Block>>,
+        options: &Options,
+    ) -> Vec<pandoc::Block> {
+        let mut result = vec![pandoc::Block::Plain(self.into_pandoc())];
         if let Some(children) = children {
-            result.extend(children.into_iter().filter_map(|b| b.to_pandoc()));
+            result.extend(children.into_iter().filter_map(|b| b.into_pandoc(options)));
         }
         result
     }
 }
 
 impl notion::RichText {
-    fn to_pandoc(self) -> pandoc::Inline {
+    fn into_pandoc(self) -> pandoc::Inline {
         match self {
             notion::RichText::Text { annotations, text } => {
                 if let Some(link) = text.link {
@@ -325,8 +1385,8 @@ impl notion::RichText {
                         annotations,
                         text: notion::Text { link: None, ..text },
                     }
-                    .to_pandoc()
-                    .to_link(link.url)
+                    .into_pandoc()
+                    .into_link(link.url)
                 } else {
                     let inline = if annotations.code {
                         pandoc::Inline::Code(pandoc::Attr::default(), text.content)
@@ -383,3 +1443,820 @@ impl notion::RichText {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn convert_block(block: serde_json::Value, args: &[&str]) -> serde_json::Value {
+        let block: notion::Block = serde_json::from_value(block).unwrap();
+        let options =
+            Args::parse_from(["notion2pandoc", "-s", "secret"].iter().chain(args)).options;
+        serde_json::to_value(block.into_pandoc(&options)).unwrap()
+    }
+
+    const BLOCK_ID: &str = "5d4ca33c-d6b7-4675-93d9-84b70af45d1c";
+
+    /// Returns a block of `kind` without children, whose content is `body`.
+    fn block(kind: &str, body: serde_json::Value) -> serde_json::Value {
+        let mut block = serde_json::json!({
+            "object": "block",
+            "id": BLOCK_ID,
+            "archived": false,
+            "has_children": false,
+            "type": kind
+        });
+        block[kind] = body;
+        block
+    }
+
+    /// Returns `parent` with `children` as fetched by `Block::fetch_recursive`.
+    fn with_children(
+        mut parent: serde_json::Value,
+        children: Vec<serde_json::Value>,
+    ) -> notion::Block {
+        parent["has_children"] = serde_json::json!(true);
+        let mut parent: notion::Block = serde_json::from_value(parent).unwrap();
+        parent.children = Some(
+            children
+                .into_iter()
+                .map(|child| serde_json::from_value(child).unwrap())
+                .collect(),
+        );
+        parent
+    }
+
+    fn text(content: &str) -> serde_json::Value {
+        serde_json::json!({
+            "type": "text",
+            "text": {"content": content, "link": null},
+            "annotations": {
+                "bold": false, "italic": false, "strikethrough": false,
+                "underline": false, "code": false, "color": "default"
+            },
+            "plain_text": content,
+            "href": null
+        })
+    }
+
+    #[test]
+    fn test_image() {
+        let image = |caption| {
+            block(
+                "image",
+                serde_json::json!({
+                    "caption": caption,
+                    "type": "external",
+                    "external": {"url": "https://example.com/a.png"}
+                }),
+            )
+        };
+
+        assert_eq!(
+            convert_block(image(serde_json::json!([])), &[]),
+            serde_json::json!({"t": "Para", "c": [
+                {"t": "Image", "c": [["", ["external"], []], [], ["https://example.com/a.png", ""]]}
+            ]})
+        );
+        assert_eq!(
+            convert_block(image(serde_json::json!([text("Fig")])), &[]),
+            serde_json::json!({"t": "Figure", "c": [
+                [BLOCK_ID, [], []],
+                [null, [{"t": "Plain", "c": [{"t": "Str", "c": "Fig"}]}]],
+                [{"t": "Plain", "c": [
+                    {"t": "Image", "c": [
+                        ["", ["external"], []],
+                        [{"t": "Str", "c": "Fig"}],
+                        ["https://example.com/a.png", ""]
+                    ]}
+                ]}]
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_code_caption() {
+        let code = |caption| {
+            block(
+                "code",
+                serde_json::json!({
+                    "caption": caption,
+                    "rich_text": [text("fn main() {}")],
+                    "language": "rust"
+                }),
+            )
+        };
+        let code_block = |attrs| serde_json::json!({"t": "CodeBlock", "c": [["", ["rust"], attrs], "fn main() {}"]});
+
+        assert_eq!(
+            convert_block(code(serde_json::json!([])), &["--code-caption", "figure"]),
+            code_block(serde_json::json!([]))
+        );
+        assert_eq!(
+            convert_block(code(serde_json::json!([text("src/main.rs")])), &[]),
+            code_block(serde_json::json!([["filename", "src/main.rs"]]))
+        );
+        assert_eq!(
+            convert_block(code(serde_json::json!([text("Entry point")])), &[]),
+            code_block(serde_json::json!([["caption", "Entry point"]]))
+        );
+        assert_eq!(
+            convert_block(
+                code(serde_json::json!([text("main.rs")])),
+                &["--code-caption", "figure"]
+            ),
+            serde_json::json!({"t": "Figure", "c": [
+                [BLOCK_ID, [], []],
+                [null, [{"t": "Plain", "c": [{"t": "Str", "c": "main.rs"}]}]],
+                [code_block(serde_json::json!([]))]
+            ]})
+        );
+        assert_eq!(
+            convert_block(
+                code(serde_json::json!([text("main.rs")])),
+                &["--code-caption", "div"]
+            ),
+            serde_json::json!({"t": "Div", "c": [["", ["code"], []], [
+                {"t": "Div", "c": [["", ["caption"], []], [
+                    {"t": "Plain", "c": [{"t": "Str", "c": "main.rs"}]}
+                ]]},
+                code_block(serde_json::json!([]))
+            ]]})
+        );
+    }
+
+    #[test]
+    fn test_diagram() {
+        let code = |language, content, caption| {
+            block(
+                "code",
+                serde_json::json!({
+                    "caption": caption,
+                    "rich_text": [text(content)],
+                    "language": language
+                }),
+            )
+        };
+
+        assert_eq!(
+            convert_block(code("mermaid", "graph TD", serde_json::json!([])), &[]),
+            serde_json::json!({"t": "CodeBlock", "c": [["", ["mermaid"], []], "graph TD"]})
+        );
+        assert_eq!(
+            convert_block(
+                code("mermaid", "graph TD", serde_json::json!([text("Flow")])),
+                &["--diagram", "class"]
+            ),
+            serde_json::json!({"t": "CodeBlock", "c": [
+                ["", ["mermaid", "diagram"], [["caption", "Flow"]]],
+                "graph TD"
+            ]})
+        );
+        assert_eq!(
+            convert_block(
+                code("plain text", "digraph { a -> b }", serde_json::json!([])),
+                &["--diagram", "figure"]
+            ),
+            serde_json::json!({"t": "Figure", "c": [
+                [BLOCK_ID, [], []],
+                [null, []],
+                [{"t": "CodeBlock", "c": [["", ["dot", "diagram"], []], "digraph { a -> b }"]}]
+            ]})
+        );
+        assert_eq!(
+            convert_block(
+                code(
+                    "plain text",
+                    "@startuml\nA -> B\n@enduml",
+                    serde_json::json!([])
+                ),
+                &["--diagram", "class"]
+            ),
+            serde_json::json!({"t": "CodeBlock", "c": [
+                ["", ["plantuml", "diagram"], []],
+                "@startuml\nA -> B\n@enduml"
+            ]})
+        );
+        assert_eq!(
+            convert_block(
+                code("python", "graph = {}", serde_json::json!([])),
+                &["--diagram", "class"]
+            ),
+            serde_json::json!({"t": "CodeBlock", "c": [["", ["python"], []], "graph = {}"]})
+        );
+        for (language, content) in [
+            ("plain text", "graphics cards"),
+            ("plain text", "strictly speaking"),
+            ("python", "@startuml = True"),
+        ] {
+            assert_eq!(
+                convert_block(
+                    code(language, content, serde_json::json!([])),
+                    &["--diagram", "class"]
+                )["c"][0][1],
+                convert_block(code(language, content, serde_json::json!([])), &[])["c"][0][1],
+                "{}",
+                content
+            );
+        }
+    }
+
+    fn table(has_row_header: bool, rows: &[[&str; 2]]) -> notion::Block {
+        with_children(
+            block(
+                "table",
+                serde_json::json!({
+                    "table_width": 2,
+                    "has_column_header": true,
+                    "has_row_header": has_row_header
+                }),
+            ),
+            rows.iter()
+                .map(|row| {
+                    block(
+                        "table_row",
+                        serde_json::json!({"cells": [[text(row[0])], [text(row[1])]]}),
+                    )
+                })
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_table() {
+        let options = Args::parse_from(["notion2pandoc", "-s", "secret"]).options;
+        let rows = [["", "Price"], ["Kale", "2.5"]];
+        let cell = |s| {
+            serde_json::json!([
+                ["", [], []],
+                {"t": "AlignDefault"},
+                1,
+                1,
+                [{"t": "Plain", "c": [{"t": "Str", "c": s}]}]
+            ])
+        };
+        let expect = |row_head_columns| {
+            serde_json::json!({"t": "Table", "c": [
+                [BLOCK_ID, [], []],
+                [null, []],
+                [[{"t": "AlignDefault"}, {"t": "ColWidthDefault"}], [{"t": "AlignDefault"}, {"t": "ColWidthDefault"}]],
+                [["", [], []], [[["", [], []], [cell(""), cell("Price")]]]],
+                [[["", [], []], row_head_columns, [], [[["", [], []], [cell("Kale"), cell("2.5")]]]]],
+                [["", [], []], []]
+            ]})
+        };
+
+        let result = table(false, &rows).into_pandoc(&options);
+        assert_eq!(serde_json::to_value(result).unwrap(), expect(0));
+        let result = table(true, &rows).into_pandoc(&options);
+        assert_eq!(serde_json::to_value(result).unwrap(), expect(1));
+    }
+
+    #[test]
+    fn test_language_class() {
+        let options = Args::parse_from([
+            "notion2pandoc",
+            "-s",
+            "secret",
+            "--language",
+            "plain text=text",
+            "--language",
+            "c++=",
+        ])
+        .options;
+        assert_eq!(
+            options.language_class("plain text"),
+            Some("text".to_string())
+        );
+        assert_eq!(options.language_class("c++"), None);
+        assert_eq!(options.language_class("c#"), Some("cs".to_string()));
+    }
+
+    #[test]
+    fn test_slugify() {
+        assert_eq!(slugify("Tags"), "tags");
+        assert_eq!(slugify("Store availability"), "store-availability");
+        assert_eq!(slugify("  Published / Date (JST) "), "published-date-jst");
+        assert_eq!(slugify("公開日"), "公開日");
+    }
+
+    #[test]
+    fn test_assign_anchors() {
+        let first = "1c3b7e2a-5d1f-4f0e-9a6b-2b8f6c4d9e01";
+        let second = "2d4c8f3b-6e2a-4a1f-8b7c-3c9a7d5e0f12";
+        let header = |id: &str| {
+            pandoc::Block::Header(
+                2,
+                pandoc::Attr(id.to_string(), vec![], vec![]),
+                vec![pandoc::Inline::Str("Usage".to_string())],
+            )
+        };
+        let link = |url: &str| {
+            pandoc::Block::Para(vec![
+                pandoc::Inline::Str("see".to_string()).into_link(url.to_string())
+            ])
+        };
+        let blocks = || {
+            vec![
+                header(first),
+                header(second),
+                link("https://www.notion.so/Page-0b4c2f1e8a7d4c6b9e3f5a2d1c0b9a88#2d4c8f3b6e2a4a1f8b7c3c9a7d5e0f12"),
+                link("https://www.notion.so/Other-5e1d3a2b8c7f4d6e9a0b1c2d3e4f5a6b#9f4c8f3b6e2a4a1f8b7c3c9a7d5e0f12"),
+            ]
+        };
+        let target = |blocks: &[pandoc::Block], i: usize| {
+            serde_json::to_value(&blocks[i]).unwrap()["c"][0]["c"][2][0].clone()
+        };
+        let id = |blocks: &mut [pandoc::Block], i: usize| {
+            let attr = blocks[i].attr_mut().unwrap();
+            (attr.0.clone(), attr.2.clone())
+        };
+
+        let mut id_blocks = blocks();
+        assign_anchors(&mut id_blocks, BlockIds::Id);
+        assert_eq!(id(&mut id_blocks, 1), (second.to_string(), vec![]));
+        assert_eq!(target(&id_blocks, 2), format!("#{}", second));
+
+        let mut slug_blocks = blocks();
+        assign_anchors(&mut slug_blocks, BlockIds::Slug);
+        let notion_id = |id: &str| vec![("data-notion-id".to_string(), id.to_string())];
+        assert_eq!(
+            id(&mut slug_blocks, 0),
+            ("usage".to_string(), notion_id(first))
+        );
+        assert_eq!(
+            id(&mut slug_blocks, 1),
+            ("usage-1".to_string(), notion_id(second))
+        );
+        assert_eq!(target(&slug_blocks, 2), "#usage-1");
+        // links to blocks outside the page are kept
+        assert_eq!(
+            target(&slug_blocks, 3),
+            "https://www.notion.so/Other-5e1d3a2b8c7f4d6e9a0b1c2d3e4f5a6b#9f4c8f3b6e2a4a1f8b7c3c9a7d5e0f12"
+        );
+    }
+
+    #[test]
+    fn test_build_toc() {
+        let header = |level, id: &str, text: &str| {
+            pandoc::Block::Header(
+                level,
+                pandoc::Attr(id.to_string(), vec![], vec![]),
+                vec![pandoc::Inline::Str(text.to_string())],
+            )
+        };
+        let mut blocks = vec![
+            pandoc::Block::Div(
+                pandoc::Attr(
+                    "".to_string(),
+                    vec!["table_of_contents".to_string()],
+                    vec![],
+                ),
+                vec![],
+            ),
+            header(2, "", "Install"),
+            header(3, "", "From source"),
+            header(2, "usage", "Usage"),
+            header(4, "", "Install"),
+        ];
+        build_toc(&mut blocks);
+        let item = |id: &str, text: &str| {
+            serde_json::json!({"t": "Plain", "c": [
+                {"t": "Link", "c": [["", [], []], [{"t": "Str", "c": text}], [format!("#{}", id), ""]]}
+            ]})
+        };
+        assert_eq!(
+            serde_json::to_value(&blocks[0]).unwrap()["c"][1],
+            serde_json::json!([{"t": "BulletList", "c": [
+                [
+                    item("install", "Install"),
+                    {"t": "BulletList", "c": [[item("from-source", "From source")]]}
+                ],
+                [
+                    item("usage", "Usage"),
+                    {"t": "BulletList", "c": [[item("install-1", "Install")]]}
+                ]
+            ]}])
+        );
+    }
+
+    #[test]
+    fn test_breadcrumb() {
+        let breadcrumb: notion::Block =
+            serde_json::from_value(block("breadcrumb", serde_json::json!({}))).unwrap();
+        let options = Args::parse_from(["notion2pandoc", "-s", "secret"]).options;
+        assert!(breadcrumb.clone().into_pandoc(&options).is_none());
+
+        let ancestors = ["Docs", "Guides"]
+            .iter()
+            .map(|title| notion::Ancestor {
+                title: serde_json::from_value(serde_json::json!([text(title)])).unwrap(),
+                url: format!("https://www.notion.so/{}", title),
+            })
+            .collect::<Vec<_>>();
+        let mut page = notion::Page {
+            blocks: vec![breadcrumb],
+        };
+        page.attach_breadcrumbs(&ancestors);
+        let link = |title: &str| {
+            serde_json::json!({"t": "Link", "c": [
+                ["", [], []],
+                [{"t": "Str", "c": title}],
+                [format!("https://www.notion.so/{}", title), ""]
+            ]})
+        };
+        assert_eq!(
+            serde_json::to_value(page.blocks.pop().unwrap().into_pandoc(&options)).unwrap(),
+            serde_json::json!({"t": "Div", "c": [
+                ["", ["breadcrumb"], []],
+                [{"t": "Plain", "c": [
+                    link("Docs"),
+                    {"t": "Space"},
+                    {"t": "Str", "c": "/"},
+                    {"t": "Space"},
+                    link("Guides")
+                ]}]
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_callout() {
+        let callout = |emoji: &str| {
+            with_children(
+                block(
+                    "callout",
+                    serde_json::json!({
+                        "rich_text": [text("Note")],
+                        "icon": {"type": "emoji", "emoji": emoji},
+                        "color": "gray_background"
+                    }),
+                ),
+                vec![block(
+                    "paragraph",
+                    serde_json::json!({"rich_text": [text("Child")], "color": "default"}),
+                )],
+            )
+        };
+        let convert = |emoji: &str, args: &[&str]| {
+            let options =
+                Args::parse_from(["notion2pandoc", "-s", "secret"].iter().chain(args)).options;
+            serde_json::to_value(callout(emoji).into_pandoc(&options)).unwrap()
+        };
+        let div = |classes: serde_json::Value, emoji: &str| {
+            serde_json::json!({"t": "Div", "c": [
+                ["", classes, [["icon", emoji]]],
+                [
+                    {"t": "Plain", "c": [{"t": "Str", "c": "Note"}]},
+                    {"t": "Para", "c": [{"t": "Str", "c": "Child"}]}
+                ]
+            ]})
+        };
+
+        assert_eq!(
+            convert("💡", &[]),
+            div(serde_json::json!(["callout"]), "💡")
+        );
+        assert_eq!(
+            convert("💡", &["--admonition", "class"]),
+            div(serde_json::json!(["callout", "tip"]), "💡")
+        );
+        assert_eq!(
+            convert("⚠️", &["--admonition", "quarto"]),
+            div(serde_json::json!(["callout-warning"]), "⚠️")
+        );
+        assert_eq!(
+            convert("🔥", &["--admonition", "quarto"]),
+            div(serde_json::json!(["callout"]), "🔥")
+        );
+        assert_eq!(
+            convert(
+                "🔥",
+                &["--admonition", "quarto", "--admonition-icon", "🔥=caution"]
+            ),
+            div(serde_json::json!(["callout-caution"]), "🔥")
+        );
+    }
+
+    #[test]
+    fn test_audio() {
+        let url = "https://s3.us-west-2.amazonaws.com/secure.notion-static.com/a.mp3?X-Amz-Expires=3600&X-Amz-Signature=0f";
+        let audio = block(
+            "audio",
+            serde_json::json!({
+                "caption": [text("Interview")],
+                "type": "file",
+                "file": {"url": url, "expiry_time": "2023-10-01T00:00:00.000Z"}
+            }),
+        );
+
+        assert_eq!(
+            convert_block(audio.clone(), &[]),
+            serde_json::json!({"t": "Para", "c": [
+                {"t": "Link", "c": [
+                    ["", ["audio", "internal"], [["type", "audio/mpeg"]]],
+                    [{"t": "Str", "c": "Interview"}],
+                    [url, ""]
+                ]}
+            ]})
+        );
+        assert_eq!(
+            convert_block(audio, &["--audio-html"]),
+            serde_json::json!({"t": "RawBlock", "c": [
+                "html",
+                format!(
+                    r#"<audio controls src="{}" title="Interview"></audio>"#,
+                    url.replace('&', "&amp;")
+                )
+            ]})
+        );
+    }
+
+    #[test]
+    fn test_file() {
+        let url = "https://s3.us-west-2.amazonaws.com/secure.notion-static.com/9c1e/report.pdf?X-Amz-Expires=3600";
+        let file = serde_json::json!({
+            "caption": [],
+            "type": "file",
+            "file": {"url": url, "expiry_time": "2023-10-01T00:00:00.000Z"},
+            "name": "Q3 report.pdf"
+        });
+        let link = |attrs| {
+            serde_json::json!({"t": "Para", "c": [
+                {"t": "Link", "c": [
+                    ["", ["file", "internal"], attrs],
+                    [{"t": "Str", "c": "Q3 report.pdf"}],
+                    [url, ""]
+                ]}
+            ]})
+        };
+        assert_eq!(
+            convert_block(block("file", file.clone()), &[]),
+            link(serde_json::json!([["type", "application/pdf"]]))
+        );
+
+        // as downloaded by `assets::download`
+        let downloaded = match serde_json::from_value(file).unwrap() {
+            notion::File::File {
+                caption,
+                file,
+                name,
+                ..
+            } => notion::File::File {
+                caption,
+                file,
+                name,
+                info: Some(notion::FileInfo {
+                    size: 52133,
+                    content_type: Some("application/x-pdf".to_string()),
+                }),
+            },
+            external => external,
+        };
+        assert_eq!(
+            serde_json::to_value(notion::Block::file_link(downloaded, "file")).unwrap(),
+            link(serde_json::json!([
+                ["type", "application/x-pdf"],
+                ["size", "52133"]
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_embed() {
+        let embed = |url: &str| block("embed", serde_json::json!({"caption": [], "url": url}));
+        let youtube = "https://www.youtube.com/watch?v=dQw4w9WgXcQ";
+        let link = |url: &str, attrs| {
+            serde_json::json!({"t": "Para", "c": [
+                {"t": "Link", "c": [["", ["embed"], attrs], [{"t": "Str", "c": url}], [url, ""]]}
+            ]})
+        };
+        let attrs = serde_json::json!([["provider", "youtube"], ["video-id", "dQw4w9WgXcQ"]]);
+
+        assert_eq!(
+            convert_block(embed(youtube), &[]),
+            link(youtube, attrs.clone())
+        );
+        assert_eq!(
+            convert_block(embed(youtube), &["--embed-html"]),
+            serde_json::json!({"t": "Div", "c": [
+                ["", ["embed"], attrs],
+                [
+                    {"t": "RawBlock", "c": [
+                        "html",
+                        r#"<iframe src="https://www.youtube.com/embed/dQw4w9WgXcQ" frameborder="0" allowfullscreen></iframe>"#
+                    ]},
+                    link(youtube, attrs.clone())
+                ]
+            ]})
+        );
+        assert_eq!(
+            convert_block(embed("https://example.com/"), &["--embed-html"]),
+            link("https://example.com/", serde_json::json!([]))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_bookmark() {
+        let bookmark = block(
+            "bookmark",
+            serde_json::json!({"caption": [], "url": "https://example.com/"}),
+        );
+        let link = |attrs| {
+            serde_json::json!({"t": "Para", "c": [
+                {"t": "Link", "c": [
+                    ["", ["bookmark"], attrs],
+                    [{"t": "Str", "c": "https://example.com/"}],
+                    ["https://example.com/", ""]
+                ]}
+            ]})
+        };
+        assert_eq!(
+            convert_block(bookmark.clone(), &[]),
+            link(serde_json::json!([]))
+        );
+
+        // previews in the cache are used without fetching the page
+        let cache = std::env::temp_dir().join(format!("notion2pandoc-{}.json", Uuid::new_v4()));
+        std::fs::write(
+            &cache,
+            serde_json::json!({"https://example.com/": {
+                "title": "Example Domain",
+                "description": null,
+                "favicon": "https://example.com/favicon.ico"
+            }})
+            .to_string(),
+        )
+        .unwrap();
+        let options = Args::parse_from([
+            "notion2pandoc",
+            "-s",
+            "secret",
+            "--bookmark-preview",
+            "--bookmark-cache",
+            cache.to_str().unwrap(),
+        ])
+        .options;
+        let mut page = notion::Page {
+            blocks: vec![serde_json::from_value(bookmark).unwrap()],
+        };
+        preview::fetch(&mut page, &options.preview).await;
+        std::fs::remove_file(&cache).unwrap();
+        assert_eq!(
+            serde_json::to_value(page.blocks.pop().unwrap().into_pandoc(&options)).unwrap(),
+            link(serde_json::json!([
+                ["title", "Example Domain"],
+                ["favicon", "https://example.com/favicon.ico"]
+            ]))
+        );
+    }
+
+    #[test]
+    fn test_raw() {
+        let code = |language: &str, caption: &str| {
+            block(
+                "code",
+                serde_json::json!({
+                    "caption": [text(caption)],
+                    "rich_text": [text("<hr>")],
+                    "language": language
+                }),
+            )
+        };
+        assert_eq!(
+            convert_block(code("html", "raw"), &["--raw-caption", "raw"]),
+            serde_json::json!({"t": "RawBlock", "c": ["html", "<hr>"]})
+        );
+        assert_eq!(
+            convert_block(code("html", "raw"), &[]),
+            serde_json::json!({"t": "CodeBlock", "c": [["", ["html"], [["caption", "raw"]]], "<hr>"]})
+        );
+        assert_eq!(
+            convert_block(code("xml", "raw"), &["--raw-caption", "raw"]),
+            serde_json::json!({"t": "CodeBlock", "c": [["", ["xml"], [["caption", "raw"]]], "<hr>"]})
+        );
+
+        assert_eq!(
+            split_raw_suffix("<kbd>Ctrl</kbd>{=html}"),
+            Some(("<kbd>Ctrl</kbd>".to_string(), "html".to_string()))
+        );
+        assert_eq!(split_raw_suffix("{=html}"), None);
+        assert_eq!(split_raw_suffix("f(x){=a b}"), None);
+        assert_eq!(split_raw_suffix("let x = {}"), None);
+    }
+
+    #[test]
+    fn test_comment() {
+        let comment: notion::Comment = serde_json::from_value(serde_json::json!({
+            "object": "comment",
+            "id": "94cc56ab-9f02-409d-9f99-1037e9fe502f",
+            "parent": {"type": "block_id", "block_id": "5d4ca33c-d6b7-4675-93d9-84b70af45d1c"},
+            "discussion_id": "f1407351-36f5-4c49-a13c-49f8ba11776d",
+            "created_time": "2022-07-15T16:52:00.000Z",
+            "last_edited_time": "2022-07-15T19:16:00.000Z",
+            "created_by": {"object": "user", "id": "9b15170a-9941-4297-8ee6-83fa7649a87a", "name": "Ada"},
+            "rich_text": [text("Cite a source")]
+        }))
+        .unwrap();
+        let options =
+            Args::parse_from(["notion2pandoc", "-s", "secret", "--timezone", "UTC"]).options;
+        let commented = |json| {
+            let mut block: notion::Block = serde_json::from_value(json).unwrap();
+            block.comments = vec![comment.clone()];
+            vec![block.into_pandoc(&options).unwrap()]
+        };
+        let span = serde_json::json!({"t": "Span", "c": [
+            ["", ["comment"], [["author", "Ada"], ["time", "2022-07-15T16:52:00+00:00"]]],
+            [{"t": "Str", "c": "Cite a source"}]
+        ]});
+
+        let mut blocks = commented(block(
+            "heading_1",
+            serde_json::json!({"rich_text": [text("Claim")], "color": "default"}),
+        ));
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap(),
+            serde_json::json!([{"t": "Header", "c": [
+                2, ["", [], []], [{"t": "Str", "c": "Claim"}, span]
+            ]}])
+        );
+        assert_eq!(
+            pandoc::stringify(match &blocks[0] {
+                pandoc::Block::Header(_, _, inlines) => inlines,
+                _ => unreachable!(),
+            }),
+            "Claim"
+        );
+        comments_to_notes(&mut blocks);
+        assert_eq!(
+            serde_json::to_value(&blocks).unwrap()[0]["c"][2][1],
+            serde_json::json!({"t": "Note", "c": [{"t": "Para", "c": [
+                {"t": "Strong", "c": [{"t": "Str", "c": "Ada:"}]},
+                {"t": "Space"},
+                {"t": "Str", "c": "Cite a source"}
+            ]}]})
+        );
+
+        // comments are not captions
+        assert_eq!(
+            serde_json::to_value(commented(block(
+                "image",
+                serde_json::json!({
+                    "caption": [],
+                    "type": "external",
+                    "external": {"url": "https://example.com/a.png"}
+                }),
+            )))
+            .unwrap(),
+            serde_json::json!([{"t": "Para", "c": [
+                {"t": "Image", "c": [["", ["external"], []], [], ["https://example.com/a.png", ""]]},
+                span
+            ]}])
+        );
+        assert_eq!(
+            serde_json::to_value(commented(block(
+                "code",
+                serde_json::json!({"caption": [], "rich_text": [text("x")], "language": "python"}),
+            )))
+            .unwrap(),
+            serde_json::json!([{"t": "Div", "c": [["", [], []], [
+                {"t": "CodeBlock", "c": [["", ["python"], []], "x"]},
+                {"t": "Para", "c": [span]}
+            ]]}])
+        );
+    }
+
+    #[test]
+    fn test_date_format() {
+        let parse = |format| {
+            Args::try_parse_from(["notion2pandoc", "-s", "secret", "--date-format", format])
+        };
+        assert_eq!(
+            parse("%Y/%m/%d %H:%M").unwrap().options.date_format,
+            "%Y/%m/%d %H:%M"
+        );
+        assert!(parse("%Q").is_err());
+        assert!(parse("%Y-%").is_err());
+
+        let options = Args::parse_from([
+            "notion2pandoc",
+            "-s",
+            "secret",
+            "--timezone",
+            "Asia/Tokyo",
+            "--date-format",
+            "%Y/%m/%d %H:%M",
+        ])
+        .options;
+        let property: notion::Property = serde_json::from_value(serde_json::json!({
+            "id": "%3Fg%5B",
+            "type": "last_edited_time",
+            "last_edited_time": "2022-07-15T16:52:00.000Z"
+        }))
+        .unwrap();
+        assert_eq!(
+            serde_json::to_value(property.into_pandoc(&options)).unwrap(),
+            serde_json::json!({"t": "MetaString", "c": "2022/07/16 01:52"})
+        );
+    }
+}
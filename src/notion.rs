@@ -1,5 +1,8 @@
+use std::collections::HashMap;
+use std::time::Duration;
+
 use chrono::{DateTime, Local};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 use uuid::Uuid;
 
 /// https://developers.notion.com/reference/intro
@@ -11,6 +14,165 @@ pub struct Page {
     pub blocks: Vec<Block>,
 }
 
+/// https://developers.notion.com/reference/page
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct PageMeta {
+    pub id: Uuid,
+    pub archived: bool,
+    pub created_time: DateTime<Local>,
+    pub last_edited_time: DateTime<Local>,
+    pub created_by: User,
+    pub last_edited_by: User,
+    pub icon: Option<Icon>,
+    pub cover: Option<File>,
+    pub parent: Parent,
+    pub properties: HashMap<String, Property>,
+}
+
+/// https://developers.notion.com/reference/parent-object
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Parent {
+    PageId {
+        page_id: Uuid,
+    },
+    DatabaseId {
+        database_id: Uuid,
+    },
+    BlockId {
+        block_id: Uuid,
+    },
+    Workspace,
+    #[serde(other)]
+    Unsupported,
+}
+
+/// A page or database containing a page, shown in breadcrumbs.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Ancestor {
+    pub title: Vec<RichText>,
+    pub url: String,
+}
+
+/// https://developers.notion.com/reference/page-property-values
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Property {
+    Title {
+        title: Vec<RichText>,
+    },
+    RichText {
+        rich_text: Vec<RichText>,
+    },
+    Number {
+        number: Option<serde_json::Number>,
+    },
+    Select {
+        select: Option<SelectOption>,
+    },
+    MultiSelect {
+        multi_select: Vec<SelectOption>,
+    },
+    Status {
+        status: Option<SelectOption>,
+    },
+    Date {
+        date: Option<Date>,
+    },
+    People {
+        people: Vec<User>,
+    },
+    Files {
+        files: Vec<File>,
+    },
+    Checkbox {
+        checkbox: bool,
+    },
+    Url {
+        url: Option<String>,
+    },
+    Email {
+        email: Option<String>,
+    },
+    PhoneNumber {
+        phone_number: Option<String>,
+    },
+    Formula {
+        formula: Formula,
+    },
+    Relation {
+        relation: Vec<PageId>,
+    },
+    Rollup {
+        rollup: Rollup,
+    },
+    CreatedTime {
+        created_time: DateTime<Local>,
+    },
+    CreatedBy {
+        created_by: User,
+    },
+    LastEditedTime {
+        last_edited_time: DateTime<Local>,
+    },
+    LastEditedBy {
+        last_edited_by: User,
+    },
+    UniqueId {
+        unique_id: UniqueId,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct SelectOption {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct Date {
+    pub start: String,
+    pub end: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct User {
+    pub id: Uuid,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Formula {
+    String { string: Option<String> },
+    Number { number: Option<serde_json::Number> },
+    Boolean { boolean: bool },
+    Date { date: Option<Date> },
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Rollup {
+    Number {
+        number: Option<serde_json::Number>,
+    },
+    Date {
+        date: Option<Date>,
+    },
+    Array {
+        array: Vec<Property>,
+    },
+    #[serde(other)]
+    Unsupported,
+}
+
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct UniqueId {
+    pub prefix: Option<String>,
+    pub number: Option<u64>,
+}
+
 // struct of Notion blocks
 
 fn deserialize_children<'de, D>(deserializer: D) -> Result<Option<Vec<Block>>, D::Error>
@@ -32,6 +194,9 @@ pub struct Block {
     pub var: Var,
     #[serde(deserialize_with = "deserialize_children", rename = "has_children")]
     pub children: Option<Vec<Block>>,
+    /// set by `Page::attach_comments`
+    #[serde(skip)]
+    pub comments: Vec<Comment>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -98,11 +263,16 @@ pub enum Var {
         #[serde(rename = "video")]
         file: File,
     },
+    Audio {
+        #[serde(rename = "audio")]
+        file: File,
+    },
     File {
         #[serde(rename = "file")]
         file: File,
     },
     #[serde(rename = "pdf")]
+    #[allow(clippy::upper_case_acronyms)]
     PDF {
         #[serde(rename = "pdf")]
         file: File,
@@ -131,6 +301,11 @@ pub enum Var {
 
     Divider,
     TableOfContents,
+    Breadcrumb {
+        /// set by `Page::attach_breadcrumbs`
+        #[serde(skip)]
+        ancestors: Vec<Ancestor>,
+    },
 
     #[serde(skip)]
     BulletedList,
@@ -139,7 +314,6 @@ pub enum Var {
 
     // ChildPage,
     // ChildDatabase,
-    // Breadcrumb,
     // Column,
     // ColumnList,
     // Template,
@@ -181,19 +355,47 @@ pub struct Equation {
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum File {
     External {
+        #[serde(default)]
         caption: Vec<RichText>,
         external: ExternalFileLink,
+        name: Option<String>,
+        /// set by `assets::download`
+        #[serde(skip)]
+        info: Option<FileInfo>,
     },
     File {
+        #[serde(default)]
         caption: Vec<RichText>,
         file: FileLink,
+        name: Option<String>,
+        /// set by `assets::download`
+        #[serde(skip)]
+        info: Option<FileInfo>,
     },
 }
 
+/// What is known about a file only after downloading it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct FileInfo {
+    pub size: u64,
+    pub content_type: Option<String>,
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 pub struct Embed {
     pub caption: Vec<RichText>,
     pub url: String,
+    /// set by `preview::fetch` for bookmarks
+    #[serde(skip)]
+    pub preview: Option<Preview>,
+}
+
+/// Open Graph metadata of a bookmarked page.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
+pub struct Preview {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub favicon: Option<String>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -213,11 +415,7 @@ pub enum LinkToPage {
 pub enum Icon {
     Emoji { emoji: String },
     External { external: Link },
-}
-
-#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
-pub struct Emoji {
-    pub emoji: String,
+    File { file: FileLink },
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -236,6 +434,9 @@ pub struct Table {
     pub table_width: u64,
     pub has_column_header: bool,
     pub has_row_header: bool,
+    /// set by `Page::attach_table_captions`
+    #[serde(skip)]
+    pub caption: Vec<RichText>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -263,6 +464,14 @@ pub enum RichText {
     },
 }
 
+/// https://developers.notion.com/reference/comment-object
+#[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
+pub struct Comment {
+    pub created_time: DateTime<Local>,
+    pub created_by: User,
+    pub rich_text: Vec<RichText>,
+}
+
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 pub struct Text {
     pub content: String,
@@ -272,9 +481,13 @@ pub struct Text {
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
 #[serde(tag = "type", rename_all = "lowercase")]
 pub enum Mention {
-    Page { page: PageId },
+    Page {
+        page: PageId,
+    },
     Date,
     User,
+    #[serde(other)]
+    Unsupported,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Clone)]
@@ -296,7 +509,9 @@ pub struct Annotations {
 use async_recursion::async_recursion;
 use futures::future::join_all;
 use itertools::join;
-use reqwest::Client;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, StatusCode};
+use tokio::sync::Semaphore;
 
 async fn fetch_blocks(id: Uuid, secret: &String) -> Vec<Block> {
     #[derive(Deserialize)]
@@ -330,23 +545,7 @@ async fn fetch_blocks(id: Uuid, secret: &String) -> Vec<Block> {
     blocks
 }
 
-pub async fn fetch_meta(id: Uuid, secret: &String) -> (String, DateTime<Local>, DateTime<Local>) {
-    #[derive(Deserialize)]
-    struct Response {
-        archived: bool,
-        created_time: DateTime<Local>,
-        last_edited_time: DateTime<Local>,
-        properties: Properties,
-    }
-    #[derive(Deserialize)]
-    struct Properties {
-        title: Title,
-    }
-    #[derive(Deserialize)]
-    struct Title {
-        title: Vec<RichText>,
-    }
-
+pub async fn fetch_meta(id: Uuid, secret: &String) -> PageMeta {
     let url = format!("https://api.notion.com/v1/pages/{}", id);
     let meta = Client::new()
         .get(&url)
@@ -355,23 +554,265 @@ pub async fn fetch_meta(id: Uuid, secret: &String) -> (String, DateTime<Local>,
         .send()
         .await
         .unwrap_or_else(|_| panic!("failed to fetch page {}", id))
-        .json::<Response>()
+        .json::<PageMeta>()
         .await
         .unwrap_or_else(|_| panic!("failed to deserialize page {}", id));
     if meta.archived {
         panic!("archived page")
     }
-    let title = join(
-        meta.properties.title.title.into_iter().map(|r| match r {
-            RichText::Text {
-                annotations: _,
-                text,
-            } => text.content,
-            _ => panic!("mention or equation in title"),
-        }),
-        "",
-    );
-    (title, meta.created_time, meta.last_edited_time)
+    meta
+}
+
+/// Returns `user` with its name filled, or as is when the integration
+/// is not allowed to read user information.
+pub async fn fetch_user(user: User, secret: &String) -> User {
+    if user.name.is_some() {
+        return user;
+    }
+    let url = format!("https://api.notion.com/v1/users/{}", user.id);
+    let response = Client::new()
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", secret))
+        .header("Notion-Version", NOTION_API_VERSION)
+        .send()
+        .await;
+    match response {
+        Ok(response) if response.status().is_success() => {
+            response.json::<User>().await.unwrap_or(user)
+        }
+        _ => user,
+    }
+}
+
+/// Users fetched by `fetch_user_cached`, shared by the pages of a database
+/// since they are often created and edited by the same users.
+pub type UserCache = HashMap<Uuid, User>;
+
+/// Returns `user` like `fetch_user`, reusing the users in `cache`.
+pub async fn fetch_user_cached(user: User, secret: &String, cache: &mut UserCache) -> User {
+    if let Some(cached) = cache.get(&user.id) {
+        return cached.clone();
+    }
+    let user = fetch_user(user, secret).await;
+    cache.insert(user.id, user.clone());
+    user
+}
+
+/// A page, database or block fetched while walking up parents.
+#[derive(Debug, Clone)]
+pub struct Node {
+    ancestor: Option<Ancestor>,
+    parent: Parent,
+}
+
+/// Nodes fetched by `fetch_ancestors`, shared by the pages of a database
+/// since they have the same ancestors.
+pub type AncestorCache = HashMap<Uuid, Option<Node>>;
+
+/// Returns the pages and databases above `parent`, the outermost first.
+///
+/// Walking up stops at the workspace or at the first parent the integration
+/// cannot read, since pages are often shared below private ones.
+pub async fn fetch_ancestors(
+    parent: &Parent,
+    secret: &String,
+    cache: &mut AncestorCache,
+) -> Vec<Ancestor> {
+    let mut ancestors = vec![];
+    let mut parent = parent.clone();
+    loop {
+        let (id, endpoint) = match parent {
+            Parent::PageId { page_id } => (page_id, "pages"),
+            Parent::DatabaseId { database_id } => (database_id, "databases"),
+            Parent::BlockId { block_id } => (block_id, "blocks"),
+            Parent::Workspace | Parent::Unsupported => break,
+        };
+        let node = match cache.get(&id) {
+            Some(node) => node.clone(),
+            None => {
+                let node = fetch_node(id, endpoint, secret).await;
+                cache.insert(id, node.clone());
+                node
+            }
+        };
+        let node = match node {
+            Some(node) => node,
+            None => break,
+        };
+        ancestors.extend(node.ancestor);
+        parent = node.parent;
+    }
+    ancestors.reverse();
+    ancestors
+}
+
+/// A page, database or block as returned by its retrieve endpoint.
+#[derive(Deserialize)]
+struct NodeResponse {
+    parent: Parent,
+    url: Option<String>,
+    // title of databases
+    title: Option<Vec<RichText>>,
+    // values of pages, but the property schema of databases
+    #[serde(default)]
+    properties: HashMap<String, serde_json::Value>,
+}
+
+impl NodeResponse {
+    fn into_node(self) -> Node {
+        let title = self.title.or_else(|| {
+            self.properties
+                .into_values()
+                .find(|p| p["type"] == "title")
+                .and_then(|mut p| serde_json::from_value(p["title"].take()).ok())
+        });
+        Node {
+            ancestor: title
+                .zip(self.url)
+                .map(|(title, url)| Ancestor { title, url }),
+            parent: self.parent,
+        }
+    }
+}
+
+async fn fetch_node(id: Uuid, endpoint: &str, secret: &String) -> Option<Node> {
+    let url = format!("https://api.notion.com/v1/{}/{}", endpoint, id);
+    let response = Client::new()
+        .get(&url)
+        .header("Authorization", format!("Bearer {}", secret))
+        .header("Notion-Version", NOTION_API_VERSION)
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) if response.status().is_success() => response,
+        // not shared with the integration
+        Ok(response)
+            if matches!(
+                response.status(),
+                StatusCode::FORBIDDEN | StatusCode::NOT_FOUND
+            ) =>
+        {
+            return None
+        }
+        Ok(response) => {
+            eprintln!("skipped ancestors from {}: {}", url, response.status());
+            return None;
+        }
+        Err(e) => {
+            eprintln!("skipped ancestors from {}: {}", url, e);
+            return None;
+        }
+    };
+    match response.json::<NodeResponse>().await {
+        Ok(response) => Some(response.into_node()),
+        Err(e) => {
+            eprintln!("skipped ancestors from {}: {}", url, e);
+            None
+        }
+    }
+}
+
+/// Returns the unresolved comments on the block or page of `id`, or nothing
+/// when the integration is not allowed to read comments.
+///
+/// Rate-limited requests are retried after the time Notion asks for.
+pub async fn fetch_comments(id: Uuid, secret: &String, limiter: &Semaphore) -> Vec<Comment> {
+    #[derive(Deserialize)]
+    struct Response {
+        has_more: bool,
+        next_cursor: Option<String>,
+        results: Vec<Comment>,
+    }
+
+    let _permit = limiter
+        .acquire()
+        .await
+        .expect("limiter should not be closed");
+    let mut comments = vec![];
+    let mut next_cursor = None;
+    loop {
+        let mut params = vec![("block_id", id.to_string())];
+        params.extend(next_cursor.clone().map(|n| ("start_cursor", n)));
+        let response = Client::new()
+            .get("https://api.notion.com/v1/comments")
+            .query(&params)
+            .header("Authorization", format!("Bearer {}", secret))
+            .header("Notion-Version", NOTION_API_VERSION)
+            .send()
+            .await
+            .unwrap_or_else(|_| panic!("failed to fetch comments of {}", id));
+        match response.status() {
+            StatusCode::FORBIDDEN | StatusCode::NOT_FOUND => return comments,
+            StatusCode::TOO_MANY_REQUESTS => {
+                let seconds = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok()?.parse().ok())
+                    .unwrap_or(1);
+                tokio::time::sleep(Duration::from_secs(seconds)).await;
+                continue;
+            }
+            status if !status.is_success() => {
+                panic!("failed to fetch comments of {}: {}", id, status)
+            }
+            _ => {}
+        }
+        let page = response
+            .json::<Response>()
+            .await
+            .unwrap_or_else(|_| panic!("failed to deserialize comments of {}", id));
+        comments.extend(page.results);
+        if !page.has_more {
+            return comments;
+        }
+        next_cursor = page.next_cursor;
+    }
+}
+
+pub async fn query_database(
+    id: Uuid,
+    secret: &String,
+    filter: Option<serde_json::Value>,
+    sorts: Option<serde_json::Value>,
+) -> Vec<PageMeta> {
+    #[derive(Deserialize)]
+    struct Response {
+        has_more: bool,
+        next_cursor: Option<String>,
+        results: Vec<PageMeta>,
+    }
+
+    let mut pages = vec![];
+    let mut has_more = true;
+    let mut next_cursor = None;
+    while has_more {
+        let url = format!("https://api.notion.com/v1/databases/{}/query", id);
+        let mut body = serde_json::Map::new();
+        if let Some(filter) = &filter {
+            body.insert("filter".to_string(), filter.clone());
+        }
+        if let Some(sorts) = &sorts {
+            body.insert("sorts".to_string(), sorts.clone());
+        }
+        if let Some(next_cursor) = next_cursor {
+            body.insert("start_cursor".to_string(), next_cursor);
+        }
+        let page = Client::new()
+            .post(&url)
+            .json(&body)
+            .header("Authorization", format!("Bearer {}", secret))
+            .header("Notion-Version", NOTION_API_VERSION)
+            .send()
+            .await
+            .unwrap_or_else(|_| panic!("failed to query database {}", id))
+            .json::<Response>()
+            .await
+            .unwrap_or_else(|_| panic!("failed to deserialize query result of {}", id));
+        next_cursor = page.next_cursor.map(serde_json::Value::String);
+        has_more = page.has_more;
+        pages.extend(page.results.into_iter().filter(|x| !x.archived));
+    }
+    pages
 }
 
 fn flatten_paragraph_block(blocks: Vec<Block>) -> Vec<Block> {
@@ -434,12 +875,14 @@ fn join_list_block(blocks: Vec<Block>) -> Vec<Block> {
             ) => result.push(Block {
                 id: block.id,
                 archived: false,
+                comments: vec![],
                 children: Some(vec![block]),
                 var: Var::BulletedList,
             }),
             (_, Var::NumberedListItem { .. }) => result.push(Block {
                 id: block.id,
                 archived: false,
+                comments: vec![],
                 children: Some(vec![block]),
                 var: Var::NumberedList,
             }),
@@ -451,6 +894,92 @@ fn join_list_block(blocks: Vec<Block>) -> Vec<Block> {
     result
 }
 
+/// Moves paragraphs starting with `prefix` right before tables into
+/// the captions of the tables, removing the prefix.
+fn attach_table_captions(blocks: Vec<Block>, prefix: &str) -> Vec<Block> {
+    let mut result: Vec<Block> = vec![];
+    for mut block in blocks {
+        block.children = block
+            .children
+            .map(|children| attach_table_captions(children, prefix));
+        if let Var::Table { table } = &mut block.var {
+            if let Some(Block {
+                var: Var::Paragraph { inline },
+                children: None,
+                ..
+            }) = result.last()
+            {
+                let plain_text = join(inline.rich_text.iter().map(|r| r.plain_text()), "");
+                if plain_text.starts_with(prefix) {
+                    if let Some(Block {
+                        var: Var::Paragraph { inline },
+                        comments,
+                        ..
+                    }) = result.pop()
+                    {
+                        table.caption = strip_rich_text(inline.rich_text, prefix.chars().count());
+                        block.comments.extend(comments);
+                    }
+                }
+            }
+        }
+        result.push(block);
+    }
+    result
+}
+
+/// Removes the first `n` characters and following whitespaces from `rich_text`.
+fn strip_rich_text(rich_text: Vec<RichText>, mut n: usize) -> Vec<RichText> {
+    let mut result = vec![];
+    for r in rich_text {
+        let len = r.plain_text().chars().count();
+        if len <= n {
+            n -= len;
+            continue;
+        }
+        match r {
+            RichText::Text {
+                annotations,
+                mut text,
+            } if result.is_empty() => {
+                text.content = text.content.chars().skip(n).collect::<String>();
+                text.content = text.content.trim_start().to_string();
+                n = 0;
+                if !text.content.is_empty() {
+                    result.push(RichText::Text { annotations, text });
+                }
+            }
+            r => {
+                n = 0;
+                result.push(r);
+            }
+        }
+    }
+    result
+}
+
+impl PageMeta {
+    pub fn title(&self) -> Vec<RichText> {
+        self.properties
+            .values()
+            .find_map(|p| match p {
+                Property::Title { title } => Some(title.clone()),
+                _ => None,
+            })
+            .expect("page should have a title property")
+    }
+}
+
+impl RichText {
+    pub fn plain_text(&self) -> String {
+        match self {
+            RichText::Text { text, .. } => text.content.clone(),
+            RichText::Mention { plain_text, .. } => plain_text.clone(),
+            RichText::Equation { equation, .. } => equation.expression.clone(),
+        }
+    }
+}
+
 impl Page {
     pub async fn fetch(id: Uuid, secret: &String) -> Self {
         let mut blocks = fetch_blocks(id, secret).await;
@@ -467,12 +996,82 @@ impl Page {
     pub fn has_toc(&self) -> bool {
         self.blocks.iter().any(|x| x.has_toc())
     }
+
+    pub fn has_breadcrumb(&self) -> bool {
+        self.blocks.iter().any(|x| x.has_breadcrumb())
+    }
+
+    pub fn attach_breadcrumbs(&mut self, ancestors: &[Ancestor]) {
+        for block in self.blocks.iter_mut() {
+            block.attach_breadcrumbs(ancestors);
+        }
+    }
+
+    /// Sets the comments on each block and returns the comments on the page itself.
+    ///
+    /// At most `concurrency` requests are sent at once, since every block
+    /// needs a request and Notion limits the rate of requests.
+    pub async fn attach_comments(
+        &mut self,
+        id: Uuid,
+        secret: &String,
+        concurrency: usize,
+        users: &mut UserCache,
+    ) -> Vec<Comment> {
+        let limiter = Semaphore::new(concurrency);
+        let mut targets = vec![];
+        for block in self.blocks.iter_mut() {
+            block.collect_comment_targets(&mut targets);
+        }
+        let (page_comments, block_comments) = futures::join!(
+            fetch_comments(id, secret, &limiter),
+            join_all(
+                targets
+                    .iter()
+                    .map(|(id, _)| fetch_comments(*id, secret, &limiter)),
+            )
+        );
+
+        let mut authors = HashMap::new();
+        for comment in page_comments.iter().chain(block_comments.iter().flatten()) {
+            if !users.contains_key(&comment.created_by.id) {
+                authors
+                    .entry(comment.created_by.id)
+                    .or_insert_with(|| comment.created_by.clone());
+            }
+        }
+        let fetched = join_all(authors.into_iter().map(|(id, user)| {
+            let limiter = &limiter;
+            async move {
+                let _permit = limiter
+                    .acquire()
+                    .await
+                    .expect("limiter should not be closed");
+                (id, fetch_user(user, secret).await)
+            }
+        }))
+        .await;
+        users.extend(fetched);
+        let resolve = |mut comment: Comment| {
+            comment.created_by = users[&comment.created_by.id].clone();
+            comment
+        };
+
+        for ((_, target), comments) in targets.into_iter().zip(block_comments) {
+            *target = comments.into_iter().map(resolve).collect();
+        }
+        page_comments.into_iter().map(resolve).collect()
+    }
+
+    pub fn attach_table_captions(&mut self, prefix: &str) {
+        self.blocks = attach_table_captions(std::mem::take(&mut self.blocks), prefix);
+    }
 }
 
 impl Block {
     #[async_recursion]
     pub async fn fetch_recursive(&mut self, secret: &String) {
-        if let Some(_) = self.children {
+        if self.children.is_some() {
             let mut children = fetch_blocks(self.id, secret).await;
             join_all(
                 children
@@ -485,15 +1084,64 @@ impl Block {
     }
 
     pub fn has_toc(&self) -> bool {
-        let mut result = match &self.var {
-            Var::TableOfContents => true,
-            _ => false,
-        };
+        let mut result = matches!(self.var, Var::TableOfContents);
         if let Some(children) = &self.children {
             result |= children.iter().any(|b| b.has_toc());
         }
         result
     }
+
+    /// Collects the comments of this block and its descendants that can be
+    /// commented in Notion, i.e. those with text or a caption.
+    fn collect_comment_targets<'a>(&'a mut self, targets: &mut Vec<(Uuid, &'a mut Vec<Comment>)>) {
+        if matches!(
+            self.var,
+            Var::Paragraph { .. }
+                | Var::Heading1 { .. }
+                | Var::Heading2 { .. }
+                | Var::Heading3 { .. }
+                | Var::Quote { .. }
+                | Var::BulletedListItem { .. }
+                | Var::NumberedListItem { .. }
+                | Var::ToggleListItem { .. }
+                | Var::Callout { .. }
+                | Var::ToDoListItem { .. }
+                | Var::Code { .. }
+                | Var::Image { .. }
+                | Var::Video { .. }
+                | Var::Audio { .. }
+                | Var::File { .. }
+                | Var::PDF { .. }
+                | Var::Embed { .. }
+                | Var::Bookmark { .. }
+        ) {
+            targets.push((self.id, &mut self.comments));
+        }
+        if let Some(children) = &mut self.children {
+            for child in children.iter_mut() {
+                child.collect_comment_targets(targets);
+            }
+        }
+    }
+
+    pub fn has_breadcrumb(&self) -> bool {
+        let mut result = matches!(self.var, Var::Breadcrumb { .. });
+        if let Some(children) = &self.children {
+            result |= children.iter().any(|b| b.has_breadcrumb());
+        }
+        result
+    }
+
+    fn attach_breadcrumbs(&mut self, ancestors: &[Ancestor]) {
+        if let Var::Breadcrumb { ancestors: a } = &mut self.var {
+            *a = ancestors.to_vec();
+        }
+        if let Some(children) = &mut self.children {
+            for child in children.iter_mut() {
+                child.attach_breadcrumbs(ancestors);
+            }
+        }
+    }
 }
 
 #[cfg(test)]
@@ -541,30 +1189,36 @@ mod tests {
 
         let input = vec![Block {
             archived: false,
+            comments: vec![],
             id: id1,
             var: var1.clone(),
             children: Some(vec![
                 Block {
                     archived: false,
+                    comments: vec![],
                     id: id2,
                     var: var2.clone(),
                     children: None,
                 },
                 Block {
                     archived: false,
+                    comments: vec![],
                     id: id3,
                     var: var3.clone(), // Quote
                     children: Some(vec![Block {
                         archived: false,
+                        comments: vec![],
                         id: id4,
                         var: var4.clone(),
                         children: Some(vec![
                             Block {
                                 archived: false,
+                                comments: vec![],
                                 id: id5,
                                 var: var5.clone(), // Quote
                                 children: Some(vec![Block {
                                     archived: false,
+                                    comments: vec![],
                                     id: id6,
                                     var: var6.clone(),
                                     children: None,
@@ -572,16 +1226,19 @@ mod tests {
                             },
                             Block {
                                 archived: false,
+                                comments: vec![],
                                 id: id7,
                                 var: var7.clone(), // Divider
                                 children: None,
                             },
                             Block {
                                 archived: false,
+                                comments: vec![],
                                 id: id8,
                                 var: var8.clone(),
                                 children: Some(vec![Block {
                                     archived: false,
+                                    comments: vec![],
                                     id: id9,
                                     var: var9.clone(),
                                     children: None,
@@ -596,33 +1253,39 @@ mod tests {
         let expect = vec![
             Block {
                 archived: false,
+                comments: vec![],
                 id: id1,
                 var: var1,
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id2,
                 var: var2,
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id3,
                 var: var3, // Quote
                 children: Some(vec![
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id4,
                         var: var4,
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id5,
                         var: var5, // Quote
                         children: Some(vec![Block {
                             archived: false,
+                            comments: vec![],
                             id: id6,
                             var: var6,
                             children: None,
@@ -630,18 +1293,21 @@ mod tests {
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id7,
                         var: var7, // Divider
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id8,
                         var: var8,
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id9,
                         var: var9,
                         children: None,
@@ -697,53 +1363,62 @@ mod tests {
         let input = vec![
             Block {
                 archived: false,
+                comments: vec![],
                 id: id1,
                 var: var1.clone(),
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id2,
                 var: var2.clone(),
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id3,
                 var: var3.clone(),
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id4,
                 var: var4.clone(), // NumberedListItem
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id5,
                 var: var5.clone(), // NumberedListItem
                 children: None,
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id6,
                 var: var6.clone(),
                 children: Some(vec![
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id7,
                         var: var7.clone(), // NumberedListItem
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id8,
                         var: var8.clone(), // NumberedListItem
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id9,
                         var: var9.clone(),
                         children: None,
@@ -755,23 +1430,27 @@ mod tests {
         let expect = vec![
             Block {
                 archived: false,
+                comments: vec![],
                 id: id1,
                 var: Var::BulletedList,
                 children: Some(vec![
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id1,
                         var: var1,
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id2,
                         var: var2,
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id3,
                         var: var3,
                         children: None,
@@ -780,17 +1459,20 @@ mod tests {
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id4,
                 var: Var::NumberedList,
                 children: Some(vec![
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id4,
                         var: var4,
                         children: None,
                     },
                     Block {
                         archived: false,
+                        comments: vec![],
                         id: id5,
                         var: var5,
                         children: None,
@@ -799,26 +1481,31 @@ mod tests {
             },
             Block {
                 archived: false,
+                comments: vec![],
                 id: id6,
                 var: Var::BulletedList,
                 children: Some(vec![Block {
                     archived: false,
+                    comments: vec![],
                     id: id6,
                     var: var6,
                     children: Some(vec![
                         Block {
                             archived: false,
+                            comments: vec![],
                             id: id7,
                             var: Var::NumberedList,
                             children: Some(vec![
                                 Block {
                                     archived: false,
+                                    comments: vec![],
                                     id: id7,
                                     var: var7, // NumberedListItem
                                     children: None,
                                 },
                                 Block {
                                     archived: false,
+                                    comments: vec![],
                                     id: id8,
                                     var: var8, // NumberedListItem
                                     children: None,
@@ -827,10 +1514,12 @@ mod tests {
                         },
                         Block {
                             archived: false,
+                            comments: vec![],
                             id: id9,
                             var: Var::BulletedList,
                             children: Some(vec![Block {
                                 archived: false,
+                                comments: vec![],
                                 id: id9,
                                 var: var9,
                                 children: None,
@@ -858,10 +1547,12 @@ mod tests {
         let input = Page {
             blocks: vec![Block {
                 archived: false,
+                comments: vec![],
                 id: id1,
                 var: var1.clone(),
                 children: Some(vec![Block {
                     archived: false,
+                    comments: vec![],
                     id: id2,
                     var: var2,
                     children: None,
@@ -874,10 +1565,12 @@ mod tests {
         let input = Page {
             blocks: vec![Block {
                 archived: false,
+                comments: vec![],
                 id: id1,
                 var: var1.clone(),
                 children: Some(vec![Block {
                     archived: false,
+                    comments: vec![],
                     id: id2,
                     var: var1,
                     children: None,
@@ -887,4 +1580,247 @@ mod tests {
 
         assert!(!input.has_toc());
     }
+
+    #[test]
+    fn test_deserialize_page_meta() {
+        let input = r#"{
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "archived": false,
+            "created_time": "2022-03-01T19:05:00.000Z",
+            "last_edited_time": "2022-07-06T20:25:00.000Z",
+            "created_by": {"object": "user", "id": "ee5f0f84-409a-440f-983a-a5315961c6e4"},
+            "last_edited_by": {"object": "user", "id": "0c3e9826-b8f7-4f73-927d-2caaf86f1103"},
+            "icon": {"type": "emoji", "emoji": "🥬"},
+            "cover": {"type": "external", "external": {"url": "https://upload.wikimedia.org/wikipedia/commons/6/62/Tuscankale.jpg"}},
+            "parent": {"type": "database_id", "database_id": "d9824bdc-8445-4327-be8b-5b47500af6ce"},
+            "properties": {
+                "Name": {
+                    "id": "title",
+                    "type": "title",
+                    "title": [{
+                        "type": "text",
+                        "text": {"content": "Tuscan kale", "link": null},
+                        "annotations": {
+                            "bold": false, "italic": false, "strikethrough": false,
+                            "underline": false, "code": false, "color": "default"
+                        },
+                        "plain_text": "Tuscan kale",
+                        "href": null
+                    }]
+                },
+                "Price": {"id": "BJXS", "type": "number", "number": 2.5},
+                "Store availability": {"id": "%3AUPp", "type": "multi_select", "multi_select": []},
+                "Food group": {"id": "A%40Hk", "type": "select", "select": {"id": "5e8e", "name": "Vegetable", "color": "red"}},
+                "Season": {"id": "Xj%3B", "type": "date", "date": {"start": "2023-09-01", "end": "2023-11-30", "time_zone": null}},
+                "Shop": {"id": "%7BJf", "type": "button", "button": {}}
+            }
+        }"#;
+        let meta: PageMeta = serde_json::from_str(input).unwrap();
+        assert_eq!(
+            meta.icon,
+            Some(Icon::Emoji {
+                emoji: "🥬".to_string()
+            })
+        );
+        assert_eq!(
+            meta.cover,
+            Some(File::External {
+                caption: vec![],
+                external: ExternalFileLink {
+                    url: "https://upload.wikimedia.org/wikipedia/commons/6/62/Tuscankale.jpg"
+                        .to_string()
+                },
+                name: None,
+                info: None,
+            })
+        );
+        assert_eq!(
+            join(meta.title().iter().map(|r| r.plain_text()), ""),
+            "Tuscan kale"
+        );
+        assert_eq!(
+            meta.parent,
+            Parent::DatabaseId {
+                database_id: Uuid::parse_str("d9824bdc-8445-4327-be8b-5b47500af6ce").unwrap()
+            }
+        );
+        assert_eq!(
+            meta.properties["Food group"],
+            Property::Select {
+                select: Some(SelectOption {
+                    name: "Vegetable".to_string()
+                })
+            }
+        );
+        assert_eq!(
+            meta.properties["Price"],
+            Property::Number {
+                number: Some(serde_json::Number::from_f64(2.5).unwrap())
+            }
+        );
+        assert_eq!(
+            meta.properties["Store availability"],
+            Property::MultiSelect {
+                multi_select: vec![]
+            }
+        );
+        assert_eq!(
+            meta.properties["Season"],
+            Property::Date {
+                date: Some(Date {
+                    start: "2023-09-01".to_string(),
+                    end: Some("2023-11-30".to_string())
+                })
+            }
+        );
+        assert_eq!(meta.properties["Shop"], Property::Unsupported);
+    }
+
+    #[test]
+    fn test_attach_table_captions() {
+        let text = |content: &str, bold| RichText::Text {
+            annotations: Annotations {
+                bold,
+                italic: false,
+                strikethrough: false,
+                underline: false,
+                code: false,
+            },
+            text: Text {
+                content: content.to_string(),
+                link: None,
+            },
+        };
+        let paragraph = |rich_text| Block {
+            archived: false,
+            comments: vec![],
+            id: Uuid::new_v4(),
+            var: Var::Paragraph {
+                inline: Inline { rich_text },
+            },
+            children: None,
+        };
+        let table = |caption| Block {
+            archived: false,
+            comments: vec![],
+            id: Uuid::nil(),
+            var: Var::Table {
+                table: Table {
+                    table_width: 2,
+                    has_column_header: false,
+                    has_row_header: false,
+                    caption,
+                },
+            },
+            children: Some(vec![]),
+        };
+
+        let not_caption = paragraph(vec![text("Prices of vegetables:", false)]);
+        let input = vec![
+            paragraph(vec![text("Table:", true), text(" Prices", false)]),
+            table(vec![]),
+            not_caption.clone(),
+            table(vec![]),
+        ];
+        let expect = vec![
+            table(vec![text("Prices", false)]),
+            not_caption,
+            table(vec![]),
+        ];
+
+        let mut page = Page { blocks: input };
+        page.attach_table_captions("Table: ");
+        assert_eq!(page.blocks, expect);
+    }
+
+    #[test]
+    fn test_node_response() {
+        let title = |content: &str| {
+            serde_json::json!([{
+                "type": "text",
+                "text": {"content": content, "link": null},
+                "annotations": {
+                    "bold": false, "italic": false, "strikethrough": false,
+                    "underline": false, "code": false, "color": "default"
+                },
+                "plain_text": content,
+                "href": null
+            }])
+        };
+        let plain_text = |node: &Node| {
+            let ancestor = node.ancestor.as_ref().unwrap();
+            join(ancestor.title.iter().map(|r| r.plain_text()), "")
+        };
+
+        let database = serde_json::json!({
+            "object": "database",
+            "id": "d9824bdc-8445-4327-be8b-5b47500af6ce",
+            "created_time": "2021-07-08T23:50:00.000Z",
+            "last_edited_time": "2021-07-08T23:50:00.000Z",
+            "icon": {"type": "emoji", "emoji": "🎉"},
+            "cover": null,
+            "url": "https://www.notion.so/d9824bdc84454327be8b5b47500af6ce",
+            "title": title("Grocery List"),
+            "description": [],
+            "is_inline": false,
+            "archived": false,
+            "parent": {"type": "page_id", "page_id": "98ad959b-2b6a-4774-80ee-00246fb0ea9b"},
+            "properties": {
+                "Name": {"id": "title", "name": "Name", "type": "title", "title": {}},
+                "Price": {"id": "BJXS", "name": "Price", "type": "number", "number": {"format": "dollar"}},
+                "Food group": {
+                    "id": "A%40Hk",
+                    "name": "Food group",
+                    "type": "select",
+                    "select": {"options": [{"id": "5e8e", "name": "Vegetable", "color": "red"}]}
+                },
+                "Last ordered": {"id": "Jsfb", "name": "Last ordered", "type": "date", "date": {}}
+            }
+        });
+        let node = serde_json::from_value::<NodeResponse>(database)
+            .unwrap()
+            .into_node();
+        assert_eq!(plain_text(&node), "Grocery List");
+        assert_eq!(
+            node.ancestor.unwrap().url,
+            "https://www.notion.so/d9824bdc84454327be8b5b47500af6ce"
+        );
+        assert_eq!(
+            node.parent,
+            Parent::PageId {
+                page_id: Uuid::parse_str("98ad959b-2b6a-4774-80ee-00246fb0ea9b").unwrap()
+            }
+        );
+
+        let page = serde_json::json!({
+            "object": "page",
+            "id": "59833787-2cf9-4fdf-8782-e53db20768a5",
+            "created_time": "2022-03-01T19:05:00.000Z",
+            "last_edited_time": "2022-07-06T20:25:00.000Z",
+            "archived": false,
+            "url": "https://www.notion.so/Tuscan-kale-598337872cf94fdf8782e53db20768a5",
+            "parent": {"type": "database_id", "database_id": "d9824bdc-8445-4327-be8b-5b47500af6ce"},
+            "properties": {
+                "Price": {"id": "BJXS", "type": "number", "number": 2.5},
+                "Recipes": {"id": "YfIu", "type": "relation", "relation": [], "has_more": false},
+                "Cost of next trip": {
+                    "id": "WOd%3B",
+                    "type": "formula",
+                    "formula": {"type": "number", "number": 0}
+                },
+                "Name": {"id": "title", "type": "title", "title": title("Tuscan kale")}
+            }
+        });
+        let node = serde_json::from_value::<NodeResponse>(page)
+            .unwrap()
+            .into_node();
+        assert_eq!(plain_text(&node), "Tuscan kale");
+        assert_eq!(
+            node.parent,
+            Parent::DatabaseId {
+                database_id: Uuid::parse_str("d9824bdc-8445-4327-be8b-5b47500af6ce").unwrap()
+            }
+        );
+    }
 }
@@ -18,23 +18,25 @@ pub struct Meta(pub HashMap<String, MetaValue>);
 
 #[derive(Debug, Serialize)]
 #[serde(tag = "t", content = "c")]
+#[allow(clippy::enum_variant_names)]
 pub enum MetaValue {
-    // MetaMap(HashMap<String, MetaValue>),
-    // MetaList(Vec<MetaValue>),
+    MetaMap(HashMap<String, MetaValue>),
+    MetaList(Vec<MetaValue>),
     MetaBool(bool),
     MetaString(String),
-    // MetaInlines(Vec<Inline>),
+    MetaInlines(Vec<Inline>),
     // MetaBlocks(Vec<Block>),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "t", content = "c")]
+#[allow(clippy::enum_variant_names, clippy::large_enum_variant)]
 pub enum Block {
     Plain(Vec<Inline>),
     Para(Vec<Inline>),
     // LineBlock(Vec<Vec<Inline>>),
     CodeBlock(Attr, String),
-    // RawBlock(Format, Text),
+    RawBlock(Format, String),
     BlockQuote(Vec<Block>),
     OrderedList(ListAttributes, Vec<Vec<Block>>),
     BulletList(Vec<Vec<Block>>),
@@ -49,11 +51,13 @@ pub enum Block {
         Vec<TableBody>,
         TableFoot,
     ),
+    Figure(Attr, Caption, Vec<Block>),
     Div(Attr, Vec<Block>),
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 #[serde(tag = "t", content = "c")]
+#[allow(clippy::enum_variant_names)]
 pub enum Inline {
     Str(String),
     Emph(Vec<Inline>),
@@ -63,26 +67,158 @@ pub enum Inline {
     // Subscript(Vec<Inline>),
     // SmallCaps(Vec<Inline>),
     // Quoted(QuoteType, Vec<Inline>),
-    // Cite(Vec<Citation>, Vec<Inline>),
+    Cite(Vec<Citation>, Vec<Inline>),
     Code(Attr, String),
     Space,
     // SoftBreak,
     // LineBreak,
     Math(MathType, String),
-    // RawInline(Format, String),
+    RawInline(Format, String),
     Link(Attr, Vec<Inline>, Target),
     Image(Attr, Vec<Inline>, Target),
-    // Note(Vec<Block>),
+    Note(Vec<Block>),
     Span(Attr, Vec<Inline>),
 }
 
 impl Inline {
-    pub fn to_link(self, url: String) -> Self {
+    pub fn into_link(self, url: String) -> Self {
         Self::Link(Attr::default(), vec![self], Target(url, "".to_string()))
     }
+
+    /// Returns the inlines directly contained in `self`.
+    fn inlines_mut(&mut self) -> Option<&mut Vec<Inline>> {
+        match self {
+            Inline::Emph(inlines)
+            | Inline::Strong(inlines)
+            | Inline::Strikeout(inlines)
+            | Inline::Link(_, inlines, _)
+            | Inline::Image(_, inlines, _)
+            | Inline::Span(_, inlines) => Some(inlines),
+            _ => None,
+        }
+    }
+}
+
+impl Block {
+    /// Returns the attributes of `self` if it has any.
+    pub fn attr_mut(&mut self) -> Option<&mut Attr> {
+        match self {
+            Block::CodeBlock(attr, _)
+            | Block::Header(_, attr, _)
+            | Block::Table(attr, ..)
+            | Block::Figure(attr, ..)
+            | Block::Div(attr, _) => Some(attr),
+            _ => None,
+        }
+    }
+
+    /// Returns the blocks directly contained in `self`.
+    fn blocks_mut(&mut self) -> Vec<&mut Block> {
+        match self {
+            Block::BlockQuote(blocks) | Block::Div(_, blocks) => blocks.iter_mut().collect(),
+            Block::OrderedList(_, items) | Block::BulletList(items) => {
+                items.iter_mut().flatten().collect()
+            }
+            Block::Figure(_, Caption(_, caption), blocks) => {
+                caption.iter_mut().chain(blocks.iter_mut()).collect()
+            }
+            Block::Table(
+                _,
+                Caption(_, caption),
+                _,
+                TableHead(_, head),
+                bodies,
+                TableFoot(_, foot),
+            ) => {
+                let rows = head
+                    .iter_mut()
+                    .chain(
+                        bodies
+                            .iter_mut()
+                            .flat_map(|TableBody(_, _, head, body)| head.iter_mut().chain(body)),
+                    )
+                    .chain(foot.iter_mut());
+                caption
+                    .iter_mut()
+                    .chain(rows.flat_map(|Row(_, cells)| {
+                        cells
+                            .iter_mut()
+                            .flat_map(|Cell(.., blocks)| blocks.iter_mut())
+                    }))
+                    .collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// Returns the inlines directly contained in `self`.
+    fn inlines_mut(&mut self) -> Option<&mut Vec<Inline>> {
+        match self {
+            Block::Plain(inlines) | Block::Para(inlines) | Block::Header(_, _, inlines) => {
+                Some(inlines)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Calls `f` on every block in `blocks` and their descendants, parents first.
+pub fn walk_blocks(blocks: &mut [Block], f: &mut dyn FnMut(&mut Block)) {
+    for block in blocks {
+        f(block);
+        for child in block.blocks_mut() {
+            walk_blocks(std::slice::from_mut(child), f);
+        }
+    }
+}
+
+/// Calls `f` on every list of inlines in `blocks` and their descendants, parents first.
+/// The contents of notes and citations are not visited.
+pub fn walk_inline_lists(blocks: &mut [Block], f: &mut dyn FnMut(&mut Vec<Inline>)) {
+    fn walk(inlines: &mut Vec<Inline>, f: &mut dyn FnMut(&mut Vec<Inline>)) {
+        f(inlines);
+        for child in inlines.iter_mut().filter_map(|i| i.inlines_mut()) {
+            walk(child, f);
+        }
+    }
+    walk_blocks(blocks, &mut |block| {
+        if let Some(inlines) = block.inlines_mut() {
+            walk(inlines, f);
+        }
+    });
+}
+
+/// Calls `f` on every inline in `blocks` and their descendants, parents first.
+/// The contents of notes and citations are not visited.
+pub fn walk_inlines(blocks: &mut [Block], f: &mut dyn FnMut(&mut Inline)) {
+    walk_inline_lists(blocks, &mut |inlines| inlines.iter_mut().for_each(&mut *f));
+}
+
+/// Converts inlines to plain text, like `stringify` of Pandoc.
+/// Comments, i.e. Spans with class `comment`, are not part of the text.
+pub fn stringify(inlines: &[Inline]) -> String {
+    inlines
+        .iter()
+        .map(|inline| match inline {
+            Inline::Str(s) | Inline::Code(_, s) | Inline::Math(_, s) => s.clone(),
+            Inline::Space => " ".to_string(),
+            Inline::RawInline(..) | Inline::Note(_) => "".to_string(),
+            Inline::Span(Attr(_, classes, _), _) if classes.iter().any(|c| c == "comment") => {
+                "".to_string()
+            }
+            Inline::Emph(inlines)
+            | Inline::Strong(inlines)
+            | Inline::Strikeout(inlines)
+            | Inline::Link(_, inlines, _)
+            | Inline::Image(_, inlines, _)
+            | Inline::Span(_, inlines)
+            | Inline::Cite(_, inlines) => stringify(inlines),
+        })
+        .collect()
 }
 
-#[derive(Debug, Default, Serialize)]
+// Notion API exposes neither alignments nor widths of table columns
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(tag = "t", content = "c")]
 pub enum Alignment {
     // AlignLeft,
@@ -92,10 +228,10 @@ pub enum Alignment {
     AlignDefault,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ListAttributes(pub u64, pub ListNumberStyle, pub ListNumberDelim);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(tag = "t", content = "c")]
 pub enum ListNumberStyle {
     #[default]
@@ -108,7 +244,7 @@ pub enum ListNumberStyle {
     // UpperAlpha,
 }
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(tag = "t", content = "c")]
 pub enum ListNumberDelim {
     #[default]
@@ -118,19 +254,36 @@ pub enum ListNumberDelim {
     // TwoParens,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Format(pub String);
 
-#[derive(Debug, Serialize, Default, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Default, PartialEq, Eq)]
 pub struct Attr(pub String, pub Vec<String>, pub Vec<(String, String)>);
 
-#[derive(Debug, Serialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
 pub struct Target(pub String, pub String);
 
-#[derive(Debug, Serialize)]
-pub struct TableCell(pub Vec<Block>);
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Citation {
+    pub citation_id: String,
+    pub citation_prefix: Vec<Inline>,
+    pub citation_suffix: Vec<Inline>,
+    pub citation_mode: CitationMode,
+    pub citation_note_num: u64,
+    pub citation_hash: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(tag = "t", content = "c")]
+pub enum CitationMode {
+    AuthorInText,
+    SuppressAuthor,
+    #[default]
+    NormalCitation,
+}
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(tag = "t", content = "c")]
 pub enum MathType {
     #[default]
@@ -140,31 +293,31 @@ pub enum MathType {
 
 // structs for Table
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Caption(pub Option<ShortCaption>, pub Vec<Block>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ShortCaption(pub Vec<Inline>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct Row(pub Attr, pub Vec<Cell>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct ColSpec(pub Alignment, pub ColWidth);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct RowHeadColumns(pub u64);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct TableHead(pub Attr, pub Vec<Row>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct TableBody(pub Attr, pub RowHeadColumns, pub Vec<Row>, pub Vec<Row>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 pub struct TableFoot(pub Attr, pub Vec<Row>);
 
-#[derive(Debug, Default, Serialize)]
+#[derive(Debug, Clone, Default, Serialize)]
 #[serde(tag = "t", content = "c")]
 pub enum ColWidth {
     // ColWidth(f64),
@@ -172,7 +325,7 @@ pub enum ColWidth {
     ColWidthDefault,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct Cell(
     pub Attr,
     pub Alignment,
@@ -181,8 +334,8 @@ pub struct Cell(
     pub Vec<Block>,
 );
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct RowSpan(pub u64);
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Clone, Serialize)]
 pub struct ColSpan(pub u64);
@@ -0,0 +1,264 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use futures::future::join_all;
+use reqwest::{Client, Url};
+
+use crate::notion;
+
+#[derive(clap::Args)]
+pub struct Options {
+    /// Fetch titles, descriptions and favicons of bookmarked pages from their Open Graph tags
+    #[clap(long, global = true)]
+    pub bookmark_preview: bool,
+    /// Keep fetched previews in this JSON file and reuse them instead of fetching again
+    #[clap(long, global = true)]
+    pub bookmark_cache: Option<PathBuf>,
+}
+
+type Cache = BTreeMap<String, notion::Preview>;
+
+/// Sets the previews of bookmarks in `page`, reusing the cache of `options`.
+///
+/// Pages that cannot be fetched, e.g. offline, are left without previews
+/// unless they are in the cache.
+pub async fn fetch(page: &mut notion::Page, options: &Options) {
+    if !options.bookmark_preview {
+        return;
+    }
+    let mut cache: Cache = match &options.bookmark_cache {
+        Some(path) if path.exists() => {
+            let json = std::fs::read_to_string(path)
+                .unwrap_or_else(|_| panic!("failed to read {}", path.display()));
+            serde_json::from_str(&json)
+                .unwrap_or_else(|_| panic!("failed to deserialize {}", path.display()))
+        }
+        _ => Cache::new(),
+    };
+
+    let mut bookmarks = vec![];
+    for block in page.blocks.iter_mut() {
+        collect_bookmarks(block, &mut bookmarks);
+    }
+    let client = Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()
+        .expect("failed to build HTTP client");
+    let missing = bookmarks
+        .iter()
+        .map(|b| b.url.clone())
+        .filter(|url| !cache.contains_key(url))
+        .collect::<BTreeSet<_>>();
+    let fetched = join_all(missing.into_iter().map(|url| {
+        let client = &client;
+        async move {
+            match fetch_preview(client, &url).await {
+                Ok(preview) => Some((url, preview)),
+                Err(e) => {
+                    eprintln!("skipped preview of {}: {}", url, e);
+                    None
+                }
+            }
+        }
+    }))
+    .await;
+    cache.extend(fetched.into_iter().flatten());
+
+    for bookmark in bookmarks {
+        bookmark.preview = cache.get(&bookmark.url).cloned();
+    }
+    if let Some(path) = &options.bookmark_cache {
+        std::fs::write(
+            path,
+            serde_json::to_string_pretty(&cache).expect("failed to serialize"),
+        )
+        .unwrap_or_else(|_| panic!("failed to write {}", path.display()));
+    }
+}
+
+fn collect_bookmarks<'a>(block: &'a mut notion::Block, bookmarks: &mut Vec<&'a mut notion::Embed>) {
+    if let notion::Var::Bookmark { embed } = &mut block.var {
+        bookmarks.push(embed);
+    }
+    if let Some(children) = &mut block.children {
+        for child in children.iter_mut() {
+            collect_bookmarks(child, bookmarks);
+        }
+    }
+}
+
+/// Bytes of a page read at most, since the tags of a preview are in `<head>`.
+const MAX_HEAD_SIZE: usize = 256 * 1024;
+
+async fn fetch_preview(client: &Client, url: &str) -> Result<notion::Preview, reqwest::Error> {
+    let mut response = client.get(url).send().await?.error_for_status()?;
+    let base = response.url().clone();
+    // the body is read in chunks to stop at `</head>` of large pages
+    let mut bytes = vec![];
+    while let Some(chunk) = response.chunk().await? {
+        let searched = bytes.len().saturating_sub(HEAD_END.len() - 1);
+        bytes.extend_from_slice(&chunk);
+        if let Some(end) = find_head_end(&bytes[searched..]) {
+            bytes.truncate(searched + end);
+            break;
+        }
+        if bytes.len() >= MAX_HEAD_SIZE {
+            bytes.truncate(MAX_HEAD_SIZE);
+            break;
+        }
+    }
+    Ok(parse(&String::from_utf8_lossy(&bytes), &base))
+}
+
+const HEAD_END: &[u8] = b"</head>";
+
+/// Returns the position just after `</head>` in `bytes`, in any case.
+fn find_head_end(bytes: &[u8]) -> Option<usize> {
+    bytes
+        .windows(HEAD_END.len())
+        .position(|w| w.eq_ignore_ascii_case(HEAD_END))
+        .map(|i| i + HEAD_END.len())
+}
+
+/// Reads the preview of a page from its Open Graph tags, falling back to
+/// `<title>` and `<meta name="description">`.
+fn parse(html: &str, base: &Url) -> notion::Preview {
+    let mut meta = BTreeMap::new();
+    let mut icon = None;
+    for (name, attrs) in tags(html) {
+        let get = |key: &str| attrs.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        match name.as_str() {
+            "meta" => {
+                if let (Some(key), Some(content)) =
+                    (get("property").or_else(|| get("name")), get("content"))
+                {
+                    meta.entry(key.to_lowercase()).or_insert(content);
+                }
+            }
+            "link" if icon.is_none() => {
+                let rel = get("rel").unwrap_or_default().to_lowercase();
+                if rel.split_whitespace().any(|r| r == "icon") {
+                    icon = get("href");
+                }
+            }
+            _ => {}
+        }
+    }
+    let title = meta.remove("og:title").or_else(|| {
+        let start = html.find("<title")?;
+        let start = start + html[start..].find('>')? + 1;
+        let end = start + html[start..].find("</title")?;
+        Some(unescape_html(html[start..end].trim()))
+    });
+    notion::Preview {
+        title: title.filter(|t| !t.is_empty()),
+        description: meta
+            .remove("og:description")
+            .or_else(|| meta.remove("description")),
+        favicon: icon
+            .and_then(|href| base.join(&href).ok())
+            .map(String::from),
+    }
+}
+
+/// Returns the names and attributes of the start tags in `html`, lowercased.
+fn tags(html: &str) -> Vec<(String, Vec<(String, String)>)> {
+    let mut tags = vec![];
+    let mut rest = html;
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let name_end = rest
+            .find(|c: char| c.is_whitespace() || c == '>' || c == '/')
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_lowercase();
+        rest = &rest[name_end..];
+        let mut attrs = vec![];
+        loop {
+            rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+            if rest.is_empty() || rest.starts_with('>') {
+                break;
+            }
+            let key_end = rest
+                .find(|c: char| c.is_whitespace() || c == '=' || c == '>')
+                .unwrap_or(rest.len());
+            let key = rest[..key_end].to_lowercase();
+            rest = rest[key_end..].trim_start();
+            let value = match rest.strip_prefix('=') {
+                Some(after) => {
+                    let after = after.trim_start();
+                    let (value, len) = match after.chars().next() {
+                        Some(q @ ('"' | '\'')) => {
+                            let end = after[1..].find(q).map_or(after.len(), |e| e + 1);
+                            (&after[1..end], (end + 1).min(after.len()))
+                        }
+                        _ => {
+                            let end = after
+                                .find(|c: char| c.is_whitespace() || c == '>')
+                                .unwrap_or(after.len());
+                            (&after[..end], end)
+                        }
+                    };
+                    rest = &after[len..];
+                    unescape_html(value)
+                }
+                None => "".to_string(),
+            };
+            attrs.push((key, value));
+        }
+        tags.push((name, attrs));
+    }
+    tags
+}
+
+fn unescape_html(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let base = Url::parse("https://example.com/posts/1").unwrap();
+        let html = r#"<!DOCTYPE html>
+            <html><head>
+            <title>Fallback</title>
+            <meta charset="utf-8">
+            <meta property="og:title" content="Tom &amp; Jerry">
+            <meta name='description' content='A cat &quot;and&quot; a mouse'>
+            <link rel="shortcut icon" href=/favicon.png>
+            </head><body></body></html>"#;
+        assert_eq!(
+            parse(html, &base),
+            notion::Preview {
+                title: Some("Tom & Jerry".to_string()),
+                description: Some("A cat \"and\" a mouse".to_string()),
+                favicon: Some("https://example.com/favicon.png".to_string()),
+            }
+        );
+        assert_eq!(
+            parse("<html><title> Plain </title></html>", &base),
+            notion::Preview {
+                title: Some("Plain".to_string()),
+                description: None,
+                favicon: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_find_head_end() {
+        assert_eq!(
+            find_head_end(b"<head><title>a</title></HEAD><body>"),
+            Some(29)
+        );
+        assert_eq!(find_head_end(b"<head><title>a</title></hea"), None);
+    }
+}